@@ -0,0 +1,271 @@
+extern crate analyzer;
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use analyzer::calibrate::Calibration;
+use analyzer::cancel::CancelToken;
+use analyzer::score::{NgramScore4, WordListScore};
+use analyzer::segment::Segmenter;
+use analyzer::simann::*;
+use analyzer::solvers::{self, CIPHERS};
+use analyzer::threads::{take_option, ThreadConfig};
+
+const MAX_RESULTS : usize = 16;
+const BUDGET_SECS : u64 = 60;
+// A job stops early once a decrypt has this much of its text covered by words, the level at
+// which p1, p2 and homophonic print High Word Coverage
+const CONFIDENT_COVERAGE : f32 = 0.75;
+const OUT_DIR : &str = "results";
+const DEFAULT_CIPHER : &str = "substitution";
+const RESULTS_WRITTEN : usize = 5;
+// How often a job looks for Ctrl-C while waiting for results
+const POLL_MILLIS : u64 = 200;
+
+struct Job {
+    path : PathBuf,
+    cipher : String,
+    budget : Duration,
+}
+
+struct Outcome {
+    best : Vec<SimulatedAnnResult>,
+    results : u64,
+    secs : f64,
+    confident : bool,
+    interrupted : bool,
+}
+
+// A cipher name in the file name, as in msg1.playfair.txt, otherwise the default
+fn cipher_hint(path : &Path, default : &str) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+
+    name.split('.')
+        .find(|part| CIPHERS.split(',').any(|c| c == *part))
+        .unwrap_or(default)
+        .to_string()
+}
+
+// Every file of a directory, or the lines FILE [CIPHER [BUDGET_SECS]] of a manifest with paths
+// relative to the manifest
+fn read_jobs(input : &Path, cipher : &str, budget : u64) -> Result<Vec<Job>, String> {
+    if input.is_dir() {
+        let mut paths : Vec<PathBuf> = fs::read_dir(input)
+            .map_err(|e| format!("Cannot read {}: {}", input.display(), e))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .collect();
+        paths.sort();
+
+        return Ok(paths.into_iter()
+            .map(|path| Job { cipher: cipher_hint(&path, cipher), path, budget: Duration::from_secs(budget) })
+            .collect());
+    }
+
+    let file = File::open(input).map_err(|e| format!("Cannot open {}: {}", input.display(), e))?;
+    let base = input.parent().unwrap_or_else(|| Path::new(""));
+    let mut jobs = Vec::new();
+
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let fields : Vec<&str> = line.split_whitespace().collect();
+
+        if fields.is_empty() || fields[0].starts_with('#') {
+            continue;
+        }
+
+        let path = base.join(fields[0]);
+        let job_cipher = fields.get(1).map(|c| c.to_string()).unwrap_or_else(|| cipher_hint(&path, cipher));
+        let job_budget = match fields.get(2) {
+            Some(b) => b.parse::<u64>().map_err(|_| format!("Bad budget in {}", line))?,
+            None => budget
+        };
+
+        jobs.push(Job { path, cipher: job_cipher, budget: Duration::from_secs(job_budget) });
+    }
+
+    Ok(jobs)
+}
+
+// Anneals on every worker thread until the budget runs out, a decrypt is covered well enough by words or
+// the batch is interrupted
+#[allow(clippy::too_many_arguments)]
+fn solve(job : &Job, text : &str, fitness : Option<&str>, confidence : f32, ng_score : &Arc<NgramScore4>,
+    wl_score : &Arc<WordListScore>, segmenter : &Segmenter, threads : &ThreadConfig, interrupt : &CancelToken)
+    -> Result<Outcome, String> {
+
+    let keyspace = solvers::keyspace(&job.cipher, text, fitness)?;
+    let start = Instant::now();
+    let cancel = CancelToken::new();
+    let (rx_chan, workers) = solvers::spawn_workers(&keyspace, ng_score, wl_score,
+        &solvers::default_config(&job.cipher), threads, &cancel);
+
+    let mut best = Vec::new();
+    let mut results = 0;
+    let mut confident = false;
+
+    while let Some(left) = job.budget.checked_sub(start.elapsed()) {
+        if interrupt.is_cancelled() {
+            break;
+        }
+
+        let res = match rx_chan.recv_timeout(left.min(Duration::from_millis(POLL_MILLIS))) {
+            Ok(res) => res,
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break
+        };
+
+        results += 1;
+        confident = res.word_coverage >= confidence;
+        handle_annealing_result(&mut best, res, MAX_RESULTS);
+
+        if confident {
+            break;
+        }
+    }
+
+    cancel.cancel();
+    drop(rx_chan);
+
+    let panicked = workers.join();
+
+    if panicked > 0 {
+        return Err(format!("{} of {} worker threads panicked", panicked, threads.threads));
+    }
+
+    if let Some(len) = best.first().map(|b : &SimulatedAnnResult| b.decrypt.len()) {
+        let calibration = Calibration::model(ng_score, len);
+
+        for res in best.iter_mut() {
+            res.segment(segmenter);
+            res.rate(&calibration, ng_score);
+        }
+    }
+
+    Ok(Outcome { best, results, secs: start.elapsed().as_secs_f64(), confident, interrupted: interrupt.is_cancelled() })
+}
+
+impl Outcome {
+    // Why the job stopped, for the result file and the summary
+    fn stop(&self) -> &'static str {
+        if self.confident {
+            "confident"
+        } else if self.interrupted {
+            "interrupted"
+        } else {
+            "budget used"
+        }
+    }
+}
+
+fn write_result(filename : &Path, job : &Job, outcome : &Outcome) -> std::io::Result<()> {
+    let mut f = File::create(filename)?;
+
+    writeln!(f, "File = {}", job.path.display())?;
+    writeln!(f, "Cipher = {}", job.cipher)?;
+    writeln!(f, "Time = {:.1}s of {}s, {} results, {}", outcome.secs, job.budget.as_secs(), outcome.results,
+        outcome.stop())?;
+
+    if let Some(best) = outcome.best.first() {
+        writeln!(f, "Key = {}", best.key)?;
+        writeln!(f, "Score = {} Coverage = {}", best.score, best.word_coverage)?;
+
+        if let Some(rating) = best.rating {
+            writeln!(f, "Rating = {}", rating)?;
+        }
+
+        writeln!(f, "Decrypt = {}", best.segmented)?;
+    }
+
+    writeln!(f)?;
+
+    for (i, res) in outcome.best.iter().take(RESULTS_WRITTEN).enumerate() {
+        writeln!(f, "{}: {}", i, res)?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+    let threads = ThreadConfig::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+    let segmenter = Segmenter::from_args(&mut args);
+
+    let out_dir = PathBuf::from(take_option(&mut args, "--out").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| String::from(OUT_DIR)));
+    let cipher = take_option(&mut args, "--cipher").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| String::from(DEFAULT_CIPHER));
+    let budget = take_option(&mut args, "--budget").unwrap_or_else(|e| panic!("{}", e)).unwrap_or(BUDGET_SECS);
+    let confidence = take_option(&mut args, "--confidence").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or(CONFIDENT_COVERAGE);
+    let fitness = take_option::<String>(&mut args, "--fitness").unwrap_or_else(|e| panic!("{}", e));
+
+    let input = match args.first() {
+        Some(input) => PathBuf::from(input),
+        None => {
+            println!("Usage: batch DIR|MANIFEST [--out DIR] [--cipher C] [--budget SECS] [--confidence COVERAGE] [--fitness SPEC] [--word-bigrams FILE] [--threads N] [--pin] [--nice]");
+            println!("Manifest lines are FILE [CIPHER [BUDGET_SECS]], CIPHER is one of {}", CIPHERS);
+            return;
+        }
+    };
+
+    let jobs = read_jobs(&input, &cipher, budget).unwrap_or_else(|e| panic!("{}", e));
+    fs::create_dir_all(&out_dir).unwrap_or_else(|e| panic!("Cannot create {}: {}", out_dir.display(), e));
+
+    let ngram_score = Arc::new(NgramScore4::create("english_quadgrams.txt"));
+    let wl_score = Arc::new(WordListScore::create("wordlist.txt"));
+
+    println!("Threads = {}", threads);
+
+    let mut summary = vec![format!("{:<32}{:<14}{:>8}{:>10}{:>12}{:>10}{:>9}  {:<12}{}",
+        "File", "Cipher", "Time", "Results", "Score", "Coverage", "English", "Stop", "Decrypt")];
+    println!("{}", summary[0]);
+
+    // Ctrl-C ends the job being solved and skips the rest, the summary still gets written
+    let interrupt = CancelToken::new();
+    interrupt.cancel_on_ctrlc();
+
+    for job in &jobs {
+        if interrupt.is_cancelled() {
+            break;
+        }
+
+        let name = job.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut text = String::new();
+
+        let outcome = File::open(&job.path)
+            .and_then(|mut f| f.read_to_string(&mut text))
+            .map_err(|e| format!("Cannot read {}: {}", job.path.display(), e))
+            .and_then(|_| solve(job, &text, fitness.as_deref(), confidence, &ngram_score, &wl_score, &segmenter, &threads, &interrupt));
+
+        let line = match outcome {
+            Ok(outcome) => {
+                let result_file = out_dir.join(format!("{}.result", name));
+                write_result(&result_file, job, &outcome)
+                    .unwrap_or_else(|e| println!("Cannot write {}: {}", result_file.display(), e));
+
+                let best = outcome.best.first().cloned().unwrap_or_default();
+
+                format!("{:<32}{:<14}{:>8}{:>10}{:>12.1}{:>10.2}{:>9}  {:<12}{}",
+                    name, job.cipher, format!("{:.1}s", outcome.secs), outcome.results, best.score,
+                    best.word_coverage, best.rating.map(|r| format!("{:.0}%", r.english_like)).unwrap_or_default(),
+                    outcome.stop(),
+                    best.segmented.chars().take(40).collect::<String>())
+            },
+            Err(e) => format!("{:<32}{:<14}failed: {}", name, job.cipher, e)
+        };
+
+        println!("{}", line);
+        summary.push(line);
+    }
+
+    let summary_file = out_dir.join("summary.txt");
+    fs::write(&summary_file, summary.join("\n") + "\n")
+        .unwrap_or_else(|e| panic!("Cannot write {}: {}", summary_file.display(), e));
+
+    std::process::exit(interrupt.exit_status(false));
+}
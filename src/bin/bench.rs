@@ -0,0 +1,261 @@
+extern crate analyzer;
+extern crate rand;
+
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::RngCore;
+
+use analyzer::cancel::CancelToken;
+use analyzer::fitness::Fitness;
+use analyzer::foursquare::{self, FourSquare, SquareCipher};
+use analyzer::fractionated::{self, Fractionated, Fractionation};
+use analyzer::homophonic::{self, Homophonic};
+use analyzer::playfair::{self, Playfair};
+use analyzer::score::{NgramScore4, WordListScore};
+use analyzer::simann::*;
+use analyzer::substitution::{self, Substitution};
+use analyzer::text::{self, CipherText};
+use analyzer::solvers::{self, BoxKeySpace, CIPHERS};
+use analyzer::threads::{take_option, ThreadConfig};
+
+const LENGTHS : &str = "100,200,400";
+const TRIALS : usize = 5;
+const BUDGET_SECS : u64 = 10;
+// A trial is solved once a decrypt gets this fraction of the plaintext letters right
+const SUCCESS_ACCURACY : f64 = 0.95;
+const HOMOPHONIC_SYMBOLS : usize = 60;
+const BENCH_PERIOD : usize = 5;
+
+// A random cipher text to solve with the plaintext its decrypt should match
+struct Case {
+    keyspace : BoxKeySpace,
+    plain : String,
+    key : String,
+}
+
+fn make_case(cipher : &str, sample : &str, fitness_spec : Option<&str>, rng : &mut dyn RngCore) -> Case {
+    let fitness = Fitness::create(fitness_spec.unwrap_or_else(|| solvers::default_fitness(cipher)),
+        "english_bigrams.txt", "english_quadgrams.txt")
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    match cipher {
+        "substitution" => {
+            let key = substitution::random_key(rng);
+            let cipher = CipherText::parse(&substitution::encrypt(sample, &key));
+            let keyspace = Arc::new(Substitution::new(cipher, fitness));
+
+            Case { keyspace, plain: String::from(sample), key }
+        },
+        "playfair" => {
+            let key = playfair::random_key(rng);
+            let plain = playfair::prepare(sample);
+            let cipher = CipherText::parse(&playfair::encrypt(&plain, &key));
+            let keyspace = Arc::new(Playfair::new(cipher, fitness));
+
+            Case { keyspace, plain, key }
+        },
+        "homophonic" => {
+            let key = homophonic::random_key(HOMOPHONIC_SYMBOLS, rng);
+            let cipher = homophonic::encrypt(sample, &key, rng);
+            let keyspace = Homophonic::new(&cipher, None, fitness).unwrap();
+
+            // The key of the solver only covers the symbols that appear
+            let key = keyspace.symbols.iter()
+                .map(|s| key.as_bytes()[s.parse::<usize>().unwrap()] as char)
+                .collect();
+
+            Case { keyspace: Arc::new(keyspace), plain: String::from(sample), key }
+        },
+        "bifid" | "trifid" => {
+            let kind = if cipher == "bifid" { Fractionation::Bifid } else { Fractionation::Trifid };
+            let key = kind.random_key(rng);
+            let plain = kind.prepare(sample);
+            let cipher = fractionated::encrypt(kind, &plain, &key, BENCH_PERIOD);
+            let keyspace = Arc::new(Fractionated::new(kind, &cipher, Some(BENCH_PERIOD), fitness));
+
+            Case { keyspace, plain, key }
+        },
+        "foursquare" | "twosquare" | "twosquare-vertical" => {
+            let kind = SquareCipher::from_name(cipher).unwrap();
+            let key = foursquare::random_key(rng);
+            let plain = playfair::prepare(sample);
+            let cipher = CipherText::parse(&foursquare::encrypt(kind, &plain, &key));
+            let keyspace = Arc::new(FourSquare::new(kind, cipher, fitness));
+
+            Case { keyspace, plain, key }
+        },
+        _ => panic!("Unknown cipher {}, expected one of {}", cipher, CIPHERS)
+    }
+}
+
+// Fraction of plaintext letters the decrypt gets right
+fn decrypt_accuracy(plain : &str, decrypt : &str) -> f64 {
+    let right = plain.bytes().zip(decrypt.bytes()).filter(|p| p.0 == p.1).count();
+    right as f64 / plain.len() as f64
+}
+
+// Fraction of the key that is right, over the letters that appear for substitution and any of
+// the equivalent grids for Playfair
+fn key_accuracy(cipher : &str, case : &Case, key : &str) -> f64 {
+    let matches = |a : &str, b : &str| a.bytes().zip(b.bytes()).filter(|p| p.0 == p.1).count();
+
+    match cipher {
+        "substitution" => {
+            let used : Vec<usize> = (0..26)
+                .filter(|i| case.plain.as_bytes().contains(&(b'A' + *i as u8)))
+                .collect();
+            let right = used.iter().filter(|i| case.key.as_bytes()[**i] == key.as_bytes()[**i]).count();
+
+            right as f64 / used.len() as f64
+        },
+        "playfair" => {
+            playfair::equivalent_keys(key).iter()
+                .map(|k| matches(k, &case.key))
+                .max()
+                .unwrap() as f64 / 25.0
+        },
+        _ => matches(key, &case.key) as f64 / case.key.len() as f64
+    }
+}
+
+struct Trial {
+    solved_secs : Option<f64>,
+    key_accuracy : f64,
+}
+
+// Anneals on every worker thread until a decrypt is close enough to the plaintext or the budget runs out
+fn run_trial(cipher : &str, case : &Case, ng_score : &Arc<NgramScore4>, wl_score : &Arc<WordListScore>,
+    config : &Arc<AnnealConfig>, threads : &ThreadConfig, budget : Duration) -> Trial {
+
+    let start = Instant::now();
+    let cancel = CancelToken::new();
+    let (tx_chan, rx_chan) = mpsc::channel();
+    let mut worker_threads = Vec::new();
+
+    for i in 0..threads.threads {
+        let tx_chan = tx_chan.clone();
+        let keyspace = case.keyspace.clone();
+        let ngram_score = ng_score.clone();
+        let wl_score = wl_score.clone();
+        let config = config.clone();
+        let cancel = cancel.clone();
+        let threads = threads.clone();
+
+        worker_threads.push(thread::spawn(move || {
+            threads.setup_worker(i);
+
+            while let Some(res) = simulated_annealing_cancel(&*keyspace, &ngram_score, &wl_score, &config, &cancel) {
+                if tx_chan.send(res).is_err() {
+                    return;
+                }
+            }
+        }));
+    }
+
+    drop(tx_chan);
+
+    let mut best : Option<SimulatedAnnResult> = None;
+    let mut solved_secs = None;
+
+    while let Some(left) = budget.checked_sub(start.elapsed()) {
+        let res = match rx_chan.recv_timeout(left) {
+            Ok(res) => res,
+            Err(_) => break
+        };
+
+        if decrypt_accuracy(&case.plain, &res.decrypt) >= SUCCESS_ACCURACY {
+            solved_secs = Some(start.elapsed().as_secs_f64());
+            best = Some(res);
+            break;
+        }
+
+        if best.as_ref().map(|b| res.score > b.score).unwrap_or(true) {
+            best = Some(res);
+        }
+    }
+
+    cancel.cancel();
+    drop(rx_chan);
+
+    for t in worker_threads {
+        t.join().unwrap_or_else(|_| println!("Failed to join thread"));
+    }
+
+    let key_accuracy = best.map(|b| key_accuracy(cipher, case, &b.key)).unwrap_or(0.0);
+
+    Trial { solved_secs, key_accuracy }
+}
+
+fn parse_list<T : std::str::FromStr>(list : &str) -> Vec<T> {
+    list.split(',')
+        .map(|v| v.parse::<T>().unwrap_or_else(|_| panic!("Bad value {}", v)))
+        .collect()
+}
+
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+    let threads = ThreadConfig::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+
+    let ciphers = take_option(&mut args, "--cipher").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| String::from(CIPHERS));
+    let lengths : Vec<usize> = parse_list(&take_option::<String>(&mut args, "--len").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| String::from(LENGTHS)));
+    let trials = take_option(&mut args, "--trials").unwrap_or_else(|e| panic!("{}", e)).unwrap_or(TRIALS);
+    let budget = take_option(&mut args, "--budget").unwrap_or_else(|e| panic!("{}", e)).unwrap_or(BUDGET_SECS);
+    let max_fail : Option<u64> = take_option(&mut args, "--max-fail").unwrap_or_else(|e| panic!("{}", e));
+    let temp : Option<i64> = take_option(&mut args, "--temp").unwrap_or_else(|e| panic!("{}", e));
+    let fitness_spec = take_option::<String>(&mut args, "--fitness").unwrap_or_else(|e| panic!("{}", e));
+
+    let corpus_file = args.first().expect("Usage: bench CORPUS [--cipher C1,C2..] [--len L1,L2..] \
+        [--trials N] [--budget SECS] [--max-fail N] [--temp T] [--fitness SPEC] [--threads N] [--pin] [--nice]");
+
+    let corpus = text::read_corpus(corpus_file);
+    let ngram_score = Arc::new(NgramScore4::create("english_quadgrams.txt"));
+    let wl_score = Arc::new(WordListScore::create("wordlist.txt"));
+    let mut rng = rand::thread_rng();
+
+    println!("Corpus = {} letters, {} trials per cell, {}s budget, solved at {:.0}% of letters right",
+        corpus.len(), trials, budget, SUCCESS_ACCURACY * 100.0);
+    println!("Threads = {}", threads);
+    println!("{:<14}{:>8}{:>10}{:>10}{:>8}{:>10}{:>14}{:>14}",
+        "Cipher", "Length", "MaxFail", "Temp", "Solved", "Rate", "Median Time", "Key Accuracy");
+
+    for cipher in ciphers.split(',') {
+        let mut config = solvers::default_config(cipher);
+        config.max_fail = max_fail.unwrap_or(config.max_fail);
+        config.temp = temp.unwrap_or(config.temp);
+        let config = Arc::new(config);
+
+        for len in &lengths {
+            let samples = text::sample_corpus(&corpus, *len, trials, &mut rng).unwrap_or_else(|e| panic!("{}", e));
+
+            let results : Vec<Trial> = samples.iter()
+                .map(|s| {
+                    let case = make_case(cipher, s, fitness_spec.as_deref(), &mut rng);
+                    run_trial(cipher, &case, &ngram_score, &wl_score, &config, &threads, Duration::from_secs(budget))
+                })
+                .collect();
+
+            let mut times : Vec<f64> = results.iter().filter_map(|r| r.solved_secs).collect();
+            times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            let median = if times.is_empty() {
+                String::from("-")
+            } else {
+                format!("{:.1}s", times[times.len() / 2])
+            };
+
+            let key_accuracy = results.iter().map(|r| r.key_accuracy).sum::<f64>() / trials as f64;
+
+            println!("{:<14}{:>8}{:>10}{:>10}{:>8}{:>10}{:>14}{:>14}",
+                cipher, len, config.max_fail, config.temp,
+                format!("{}/{}", times.len(), trials),
+                format!("{:.0}%", times.len() as f64 * 100.0 / trials as f64),
+                median,
+                format!("{:.2}", key_accuracy));
+        }
+    }
+}
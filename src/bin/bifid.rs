@@ -0,0 +1,113 @@
+extern crate analyzer;
+extern crate rand;
+
+use std::fs::File;
+use std::io::Read;
+use std::sync::Arc;
+
+use analyzer::fractionated::{self, Fractionated, Fractionation};
+use analyzer::fitness::Fitness;
+use analyzer::score::{NgramScore4, WordListScore};
+use analyzer::simann::*;
+use analyzer::segment::Segmenter;
+use analyzer::solvers;
+use analyzer::stop::StopCriteria;
+use analyzer::calibrate::Calibration;
+use analyzer::cancel::CancelToken;
+use analyzer::threads::{take_option, ThreadConfig};
+
+const HIGH_COVERAGE : f32 = 0.75;
+const PERIODS_SHOWN : usize = 5;
+
+fn print_best(keyspace: &Fractionated, all_results: &[SimulatedAnnResult]) {
+    if let Some(best) = all_results.first() {
+        println!("Best Key:\n{}", keyspace.kind.format_key(&best.key));
+    }
+}
+
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+    let threads = ThreadConfig::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+    let segmenter = Segmenter::from_args(&mut args);
+    let criteria = StopCriteria::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+    let period = take_option::<usize>(&mut args, "--period").unwrap_or_else(|e| panic!("{}", e));
+    let max_fail = take_option::<u64>(&mut args, "--max-fail").unwrap_or_else(|e| panic!("{}", e));
+    let kind = match args.iter().position(|a| a == "--trifid") {
+        Some(i) => {
+            args.remove(i);
+            Fractionation::Trifid
+        },
+        None => Fractionation::Bifid
+    };
+
+    let fitness_spec = take_option(&mut args, "--fitness").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| String::from(solvers::default_fitness(kind.name())));
+
+    let filename = match args.first() {
+        Some(filename) => filename.clone(),
+        None => {
+            println!("Usage: bifid FILE [--trifid] [--period N] [--max-fail N] [--fitness SPEC] [--word-bigrams FILE]");
+            return;
+        }
+    };
+
+    let mut file = File::open(&filename).unwrap_or_else(|_| panic!("Cannot open {}", filename));
+
+    let mut text = String::new();
+
+    file.read_to_string(&mut text).unwrap();
+
+    let ngram_score = NgramScore4::create("english_quadgrams.txt");
+
+    let wl_score = WordListScore::create("wordlist.txt");
+
+    let fitness = Fitness::create(&fitness_spec, "english_bigrams.txt", "english_quadgrams.txt")
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    println!("Fitness = {}", fitness);
+
+    let keyspace = Fractionated::new(kind, &text, period, fitness);
+
+    if keyspace.cipher.is_empty() {
+        println!("No {} cipher letters in {}", kind.name(), filename);
+        std::process::exit(1);
+    }
+
+    println!("Cipher = {}", keyspace.cipher);
+    println!("Cipher Length = {}", keyspace.cipher.len());
+
+    // Period 0 is the whole text in one block
+    println!("Period Scores = {}", fractionated::period_scores(kind, &keyspace.cipher, fractionated::MAX_PERIOD)
+        .iter()
+        .take(PERIODS_SHOWN)
+        .map(|p| format!("({}, {:.2})", p.0, p.1))
+        .collect::<Vec<_>>()
+        .join(" "));
+    println!("Period = {}{}", keyspace.period, if period.is_some() { "" } else { " (detected)" });
+
+    let calibration = Calibration::from_args(&mut args, &ngram_score, keyspace.cipher.len()).unwrap_or_else(|e| {
+        println!("{}", e);
+        std::process::exit(1)
+    });
+    println!("Calibration = {}", calibration);
+
+    let mut config = solvers::default_config(kind.name());
+    config.max_fail = max_fail.unwrap_or(config.max_fail);
+
+    let run = solvers::Run {
+        keyspace: Arc::new(keyspace),
+        ng_score: Arc::new(ngram_score),
+        wl_score: Arc::new(wl_score),
+        config,
+        segmenter,
+        calibration,
+        criteria,
+        high_coverage: HIGH_COVERAGE,
+        rescore: None,
+    };
+
+    let cancel = CancelToken::new();
+    cancel.cancel_on_ctrlc();
+
+    std::process::exit(run.run(&threads, &cancel, print_best));
+}
@@ -0,0 +1,282 @@
+extern crate analyzer;
+extern crate rand;
+
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use analyzer::calibrate::Calibration;
+use analyzer::cancel::CancelToken;
+use analyzer::distrib::{self, JobSpec, Message, HEARTBEAT_SECS, DEAD_SECS};
+use analyzer::score::{NgramScore4, WordListScore};
+use analyzer::segment::Segmenter;
+use analyzer::simann::*;
+use analyzer::solvers::{self, BoxKeySpace};
+use analyzer::threads::take_option;
+
+const MAX_RESULTS : usize = 16;
+const PRINT_SECS : u64 = 20;
+const PRINT_RESULTS : usize = 3;
+const HIGH_COVERAGE : f32 = 0.75;
+
+struct Job {
+    spec : JobSpec,
+    keyspace : BoxKeySpace,
+    calibration : Calibration,
+    best : Mutex<Vec<SimulatedAnnResult>>,
+}
+
+struct Worker {
+    name : String,
+    job : usize,
+    threads : usize,
+    results : u64,
+    last_results : u64,
+    connected : bool,
+    // The latest connection of the worker, an older one ending after a reconnect leaves it connected
+    connection : usize,
+}
+
+struct Coordinator {
+    jobs : Vec<Job>,
+    workers : Mutex<Vec<Worker>>,
+    connections : AtomicUsize,
+    ngram : NgramScore4,
+    wl : WordListScore,
+    segmenter : Segmenter,
+}
+
+impl Coordinator {
+    // New workers take the job with the fewest connected threads, returns the job and the connection id
+    fn assign(&self, name : &str, threads : usize) -> (usize, usize) {
+        let mut workers = self.workers.lock().unwrap();
+        let connection = self.connections.fetch_add(1, Ordering::Relaxed);
+
+        let job = (0..self.jobs.len())
+            .min_by_key(|j| workers.iter().filter(|w| w.connected && w.job == *j).map(|w| w.threads).sum::<usize>())
+            .unwrap();
+
+        match workers.iter_mut().find(|w| w.name == name) {
+            Some(w) => {
+                w.job = job;
+                w.threads = threads;
+                w.connected = true;
+                w.connection = connection;
+            },
+            None => workers.push(Worker { name: String::from(name), job, threads, results: 0, last_results: 0,
+                connected: true, connection })
+        }
+
+        (job, connection)
+    }
+
+    fn disconnect(&self, connection : usize) {
+        if let Some(w) = self.workers.lock().unwrap().iter_mut().find(|w| w.connection == connection) {
+            w.connected = false;
+        }
+    }
+
+    fn add_result(&self, name : &str, job : usize, key : &str) -> Result<(), String> {
+        let job = self.jobs.get(job).ok_or_else(|| format!("No job {}", job))?;
+
+        if !solvers::check_key(&job.spec.cipher, &job.keyspace, key) {
+            return Err(format!("Bad key {}", key));
+        }
+
+        // Results are rescored here so a worker with other tables cannot skew the leaderboard
+        let mut res = evaluate_key(&*job.keyspace, &self.ngram, &self.wl, key);
+        res.segment(&self.segmenter);
+        res.rate(&job.calibration, &self.ngram);
+
+        if res.word_coverage > HIGH_COVERAGE {
+            println!("High Word Coverage from {}: {}", name, res);
+        }
+
+        handle_annealing_result(&mut job.best.lock().unwrap(), res, MAX_RESULTS);
+
+        if let Some(w) = self.workers.lock().unwrap().iter_mut().find(|w| w.name == name) {
+            w.results += 1;
+        }
+
+        Ok(())
+    }
+
+    fn print(&self, final_results : bool, secs : f32) {
+        for (i, job) in self.jobs.iter().enumerate() {
+            let best = job.best.lock().unwrap();
+            let shown = if final_results { best.len() } else { PRINT_RESULTS.min(best.len()) };
+
+            println!("Job {} ({}):", i, job.spec.cipher);
+            print_results(&best[..shown]);
+        }
+
+        let mut workers = self.workers.lock().unwrap();
+
+        println!("{:<24}{:>6}{:>10}{:>12}{:>10}", "Worker", "Job", "Threads", "Results", "Rate");
+
+        for w in workers.iter_mut() {
+            println!("{:<24}{:>6}{:>10}{:>12}{:>10}",
+                w.name, w.job, w.threads, w.results,
+                if w.connected {
+                    format!("{:.1}/s", (w.results - w.last_results) as f32 / secs)
+                } else {
+                    String::from("gone")
+                });
+
+            w.last_results = w.results;
+        }
+    }
+}
+
+fn handle_worker(coord : &Coordinator, mut stream : TcpStream) -> Result<(), String> {
+    stream.set_read_timeout(Some(Duration::from_secs(DEAD_SECS))).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+
+    let (name, threads) = match distrib::read_message(&mut reader)? {
+        Message::Hello { name, threads } => (name, threads),
+        _ => return Err(String::from("Expected HELLO"))
+    };
+
+    let (job, connection) = coord.assign(&name, threads);
+    let spec = &coord.jobs[job].spec;
+
+    println!("Worker {} joined with {} threads, given job {}", name, threads, job);
+
+    distrib::write_message(&mut stream, &Message::Job(JobSpec {
+        id: job,
+        cipher: spec.cipher.clone(),
+        fitness: spec.fitness.clone(),
+        max_fail: spec.max_fail,
+        temp: spec.temp,
+        text: spec.text.clone(),
+    })).map_err(|e| e.to_string())?;
+
+    let alive = Arc::new(AtomicBool::new(true));
+    let heartbeat_alive = alive.clone();
+    let mut heartbeat_stream = stream.try_clone().map_err(|e| e.to_string())?;
+
+    thread::spawn(move || {
+        while heartbeat_alive.load(Ordering::Relaxed) {
+            if distrib::write_message(&mut heartbeat_stream, &Message::Heartbeat).is_err() {
+                return;
+            }
+
+            thread::sleep(Duration::from_secs(HEARTBEAT_SECS));
+        }
+    });
+
+    let res = loop {
+        match distrib::read_message(&mut reader) {
+            Ok(Message::Heartbeat) => continue,
+            Ok(Message::Result { job, key }) => {
+                if let Err(e) = coord.add_result(&name, job, &key) {
+                    break Err(format!("Worker {}: {}", name, e));
+                }
+            },
+            Ok(_) => break Err(format!("Worker {}: unexpected message", name)),
+            Err(e) => break Err(format!("Worker {} lost: {}", name, e))
+        }
+    };
+
+    alive.store(false, Ordering::Relaxed);
+    coord.disconnect(connection);
+    let _ = stream.shutdown(Shutdown::Both);
+
+    res
+}
+
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+
+    let cipher = take_option(&mut args, "--cipher").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| String::from("substitution"));
+    let fitness = take_option(&mut args, "--fitness").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| String::from(solvers::default_fitness(&cipher)));
+    let bind = take_option(&mut args, "--bind").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| format!("0.0.0.0:{}", distrib::DEFAULT_PORT));
+    let mut config = solvers::default_config(&cipher);
+    config.max_fail = take_option(&mut args, "--max-fail").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or(config.max_fail);
+    config.temp = take_option(&mut args, "--temp").unwrap_or_else(|e| panic!("{}", e)).unwrap_or(config.temp);
+    let segmenter = Segmenter::from_args(&mut args);
+
+    if args.is_empty() {
+        println!("Usage: coordinator FILE.. [--cipher C] [--fitness SPEC] [--bind ADDR:PORT] [--max-fail N] [--temp T] [--word-bigrams FILE]");
+        println!("CIPHER is one of {}, every FILE is a job", solvers::CIPHERS);
+        return;
+    }
+
+    let ngram_score = NgramScore4::create("english_quadgrams.txt");
+
+    let jobs = args.iter().enumerate().map(|(id, filename)| {
+        let mut text = String::new();
+        File::open(filename)
+            .unwrap_or_else(|_| panic!("Cannot open {}", filename))
+            .read_to_string(&mut text)
+            .unwrap();
+
+        let keyspace = solvers::keyspace(&cipher, &text, Some(&fitness)).unwrap_or_else(|e| panic!("{}", e));
+        let len = keyspace.decrypt(&keyspace.generate_key(&mut rand::thread_rng())).len();
+
+        println!("Job {} = {}", id, filename);
+
+        Job {
+            spec: JobSpec { id, cipher: cipher.clone(), fitness: fitness.clone(), max_fail: config.max_fail, temp: config.temp, text },
+            calibration: Calibration::model(&ngram_score, len),
+            keyspace,
+            best: Mutex::new(Vec::new()),
+        }
+    }).collect();
+
+    let coord = Arc::new(Coordinator {
+        jobs,
+        workers: Mutex::new(Vec::new()),
+        connections: AtomicUsize::new(0),
+        ngram: ngram_score,
+        wl: WordListScore::create("wordlist.txt"),
+        segmenter,
+    });
+
+    let listener = TcpListener::bind(&bind).unwrap_or_else(|e| panic!("Cannot bind {}: {}", bind, e));
+    println!("Waiting for workers on {}", bind);
+
+    let accept_coord = coord.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let coord = accept_coord.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_worker(&coord, stream) {
+                            println!("{}", e);
+                        }
+                    });
+                },
+                Err(e) => println!("Connection failed: {}", e)
+            }
+        }
+    });
+
+    let cancel = CancelToken::new();
+    cancel.cancel_on_ctrlc();
+
+    let mut print_time = Instant::now();
+
+    while !cancel.is_cancelled() {
+        thread::sleep(Duration::from_millis(200));
+
+        if print_time.elapsed() >= Duration::from_secs(PRINT_SECS) {
+            coord.print(false, print_time.elapsed().as_secs_f32());
+            print_time = Instant::now();
+        }
+    }
+
+    coord.print(true, print_time.elapsed().as_secs_f32());
+
+    std::io::stdout().flush().unwrap();
+    std::process::exit(cancel.exit_status(false));
+}
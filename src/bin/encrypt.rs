@@ -0,0 +1,175 @@
+extern crate analyzer;
+extern crate rand;
+
+use std::fs::File;
+use std::io::Read;
+
+use analyzer::foursquare::{self, SquareCipher};
+use analyzer::fractionated::{self, Fractionation};
+use analyzer::hill::{self, Matrix};
+use analyzer::homophonic;
+use analyzer::playfair::{self, PLAYFAIR_ALPHABET};
+use analyzer::solvers;
+use analyzer::substitution::{self, ALPHABET};
+use analyzer::text::CipherText;
+use analyzer::threads::take_option;
+
+const CIPHERS : &str = "substitution, playfair, homophonic, bifid, trifid, foursquare, twosquare, twosquare-vertical, hill";
+const HOMOPHONIC_SYMBOLS : usize = 60;
+const DEFAULT_PERIOD : usize = 5;
+const DEFAULT_HILL_SIZE : usize = 3;
+
+// Keys must use every letter of the alphabet once
+fn check_permutation(key : &str, alphabet : &str) -> Result<(), String> {
+    let mut sorted : Vec<char> = key.chars().collect();
+    let mut expected : Vec<char> = alphabet.chars().collect();
+    sorted.sort();
+    expected.sort();
+
+    if sorted == expected {
+        Ok(())
+    } else {
+        Err(format!("Key {} is not a permutation of {}", key, alphabet))
+    }
+}
+
+// Substitution keys are key[plain] = cipher as printed by p1, Playfair keys are the square
+// printed by p2 or a keyword, homophonic keys hold the letter of each two digit symbol, Bifid and
+// Trifid keys are the square or cube as printed by bifid or a keyword, Four-square and Two-square
+// keys are the 50 letters of both squares as printed by foursquare or two comma separated keywords,
+// Hill keys are the matrix as 4, 9 or 16 letters or numbers row by row
+fn run(cipher : &str, key : &str, decrypt : bool, period : usize, size : usize, text : &str) -> Result<String, String> {
+    let mut rng = rand::thread_rng();
+    let key = key.to_uppercase();
+
+    match cipher {
+        "substitution" => {
+            let key = if key == "RANDOM" { substitution::random_key(&mut rng) } else { key };
+            check_permutation(&key, ALPHABET)?;
+            eprintln!("Key = {}", key);
+
+            if decrypt {
+                Ok(substitution::decrypt(text, &key))
+            } else {
+                Ok(substitution::encrypt(text, &key))
+            }
+        },
+        "playfair" => {
+            let key = if key == "RANDOM" { playfair::random_key(&mut rng) } else { playfair::keyword_square(&key) };
+            check_permutation(&key, PLAYFAIR_ALPHABET)?;
+            eprintln!("Key = {}", key);
+
+            if decrypt {
+                let cipher = solvers::playfair_text(text)?;
+                Ok(cipher.render(&playfair::decrypt(&cipher.letters, &key)))
+            } else {
+                Ok(playfair::encrypt(&playfair::prepare(text), &key))
+            }
+        },
+        "homophonic" => {
+            let key = if key == "RANDOM" { homophonic::random_key(HOMOPHONIC_SYMBOLS, &mut rng) } else { key };
+
+            // Decrypting only needs the symbols in the cipher, solved keys mark the rest with ?
+            if let Some(c) = ALPHABET.chars().find(|c| !decrypt && !key.contains(*c)) {
+                return Err(format!("Key has no symbol for {}", c));
+            }
+
+            eprintln!("Key = {}", key);
+
+            if decrypt {
+                homophonic::decrypt(text, &key)
+            } else {
+                Ok(homophonic::encrypt(text, &key, &mut rng))
+            }
+        },
+        "bifid" | "trifid" => {
+            let kind = if cipher == "bifid" { Fractionation::Bifid } else { Fractionation::Trifid };
+            let key = if key == "RANDOM" { kind.random_key(&mut rng) } else { kind.keyword_key(&key) };
+            check_permutation(&key, kind.alphabet())?;
+            eprintln!("Key = {}", key);
+            eprintln!("Period = {}", period);
+
+            if decrypt {
+                Ok(fractionated::decrypt(kind, &kind.cipher_letters(text), &key, period))
+            } else {
+                Ok(fractionated::encrypt(kind, &kind.prepare(text), &key, period))
+            }
+        },
+        "foursquare" | "twosquare" | "twosquare-vertical" => {
+            let kind = SquareCipher::from_name(cipher).unwrap();
+            let key = if key == "RANDOM" {
+                foursquare::random_key(&mut rng)
+            } else if let Some((k1, k2)) = key.split_once(',') {
+                foursquare::keyword_key(k1, k2)
+            } else if key.len() == 50 {
+                foursquare::keyword_key(&key[..25], &key[25..])
+            } else {
+                return Err(String::from("Key needs two squares or keywords separated by a comma"));
+            };
+            check_permutation(&key[..25], PLAYFAIR_ALPHABET)?;
+            check_permutation(&key[25..], PLAYFAIR_ALPHABET)?;
+            eprintln!("Key = {}", key);
+
+            if decrypt {
+                Ok(foursquare::decrypt(kind, &playfair::merge_j(text), &key))
+            } else {
+                Ok(foursquare::encrypt(kind, &playfair::prepare(text), &key))
+            }
+        },
+        "hill" => {
+            let key = if key == "RANDOM" { Matrix::random_invertible(size, &mut rng) } else { Matrix::parse(&key)? };
+            eprintln!("Key = {}", key.letters());
+
+            if decrypt {
+                hill::decrypt(&CipherText::parse(text).letters, &key)
+            } else {
+                hill::encrypt(&hill::prepare(text, key.n), &key)
+            }
+        },
+        _ => Err(format!("Unknown cipher {}, expected one of {}", cipher, CIPHERS))
+    }
+}
+
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+    let decrypt = match args.iter().position(|a| a == "--decrypt") {
+        Some(i) => {
+            args.remove(i);
+            true
+        },
+        None => false
+    };
+    let period = take_option::<usize>(&mut args, "--period").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or(DEFAULT_PERIOD);
+    let size = take_option::<usize>(&mut args, "--size").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or(DEFAULT_HILL_SIZE);
+
+    if !hill::SIZES.contains(&size) {
+        panic!("--size needs 2, 3 or 4");
+    }
+
+    if args.len() < 2 {
+        println!("Usage: encrypt [--decrypt] [--period N] [--size N] CIPHER KEY|random [FILE]");
+        println!("CIPHER is one of {}, the text is read from stdin without FILE", CIPHERS);
+        println!("--period sets the Bifid and Trifid period, {} by default, 0 for the whole text", DEFAULT_PERIOD);
+        println!("--size sets the size of a random Hill key, {} by default", DEFAULT_HILL_SIZE);
+        return;
+    }
+
+    let mut text = String::new();
+
+    match args.get(2) {
+        Some(filename) => File::open(filename)
+            .unwrap_or_else(|_| panic!("Cannot open {}", filename))
+            .read_to_string(&mut text),
+        None => std::io::stdin().read_to_string(&mut text)
+    }.unwrap();
+
+    match run(&args[0], &args[1], decrypt, period, size, text.trim_end()) {
+        Ok(out) => println!("{}", out),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
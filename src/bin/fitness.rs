@@ -0,0 +1,89 @@
+extern crate analyzer;
+extern crate rand;
+
+use analyzer::calibrate::Calibration;
+use analyzer::fitness::{self, Fitness, MEASURES};
+use analyzer::score::{NgramScore4, WordListScore};
+use analyzer::text;
+
+const LENGTHS : &str = "50,100,200,400";
+const SAMPLES : usize = 200;
+// Near misses per sample and the most key swaps in each
+const MISSES : usize = 10;
+const MAX_SWAPS : usize = 2;
+
+// Reports how well each measure, and any --fitness specs, tell plaintext from near miss decrypts
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+    let mut specs = Vec::new();
+    let mut lengths = String::from(LENGTHS);
+
+    while let Some(i) = args.iter().position(|a| a == "--fitness" || a == "--len") {
+        let value = args.get(i + 1).cloned().unwrap_or_else(|| panic!("{} needs a value", args[i]));
+
+        if args[i] == "--fitness" {
+            specs.push(value);
+        } else {
+            lengths = value;
+        }
+
+        args.drain(i..=i + 1);
+    }
+
+    let corpus_file = args.first().expect("Usage: fitness CORPUS [--len L1,L2..] [--fitness SPEC]..");
+    let lengths : Vec<usize> = lengths.split(',')
+        .map(|l| l.parse::<usize>().expect("Bad length"))
+        .collect();
+
+    let corpus = text::read_corpus(corpus_file);
+    let ngram_score = NgramScore4::create("english_quadgrams.txt");
+    let wl_score = WordListScore::create("wordlist.txt");
+
+    specs.splice(0..0, MEASURES.iter().map(|m| String::from(m.name())));
+
+    let fitnesses : Vec<Fitness> = specs.iter()
+        .map(|s| Fitness::create(s, "english_bigrams.txt", "english_quadgrams.txt")
+            .unwrap_or_else(|e| panic!("{}", e)))
+        .collect();
+
+    let mut rng = rand::thread_rng();
+
+    let samples : Vec<Vec<String>> = lengths.iter()
+        .map(|l| text::sample_corpus(&corpus, *l, SAMPLES, &mut rng).unwrap_or_else(|e| panic!("{}", e)))
+        .collect();
+
+    println!("Corpus = {} letters, {} samples per length, {} near misses of up to {} swaps each",
+        corpus.len(), SAMPLES, MISSES, MAX_SWAPS);
+    println!("Each cell is the fraction of near misses scored below the plaintext (mean gap / std)");
+
+    print!("{:<30}", "Fitness");
+    for l in &lengths {
+        print!("{:>18}", format!("len {}", l));
+    }
+    println!();
+
+    for f in &fitnesses {
+        print!("{:<30}", f.to_string());
+
+        for s in &samples {
+            let sep = fitness::separation(s, MISSES, MAX_SWAPS, &mut rng,
+                |t| f.score(&ngram_score, &wl_score, t, None));
+
+            print!("{:>18}", format!("{:.3} ({:.2})", sep.accuracy, sep.gap_sigma));
+        }
+
+        println!();
+    }
+
+    println!();
+    println!("Quadgram scores of English and random letters, from the quadgram table and sampled from the corpus");
+    println!("{:<10}{:>22}{:>22}{:>22}{:>22}", "Length", "English Model", "English Corpus", "Random Model", "Random Corpus");
+
+    for l in &lengths {
+        let model = Calibration::model(&ngram_score, *l);
+        let sampled = Calibration::sample(&ngram_score, &corpus, *l, SAMPLES, &mut rng).unwrap();
+
+        println!("{:<10}{:>22}{:>22}{:>22}{:>22}", l,
+            model.english.to_string(), sampled.english.to_string(), model.random.to_string(), sampled.random.to_string());
+    }
+}
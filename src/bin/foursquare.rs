@@ -0,0 +1,109 @@
+extern crate analyzer;
+extern crate rand;
+
+use std::fs::File;
+use std::io::Read;
+use std::sync::Arc;
+
+use analyzer::foursquare::{FourSquare, SquareCipher};
+use analyzer::fitness::Fitness;
+use analyzer::score::{NgramScore4, WordListScore};
+use analyzer::simann::SimulatedAnnResult;
+use analyzer::segment::Segmenter;
+use analyzer::solvers;
+use analyzer::stop::StopCriteria;
+use analyzer::calibrate::Calibration;
+use analyzer::text::CipherText;
+use analyzer::cancel::CancelToken;
+use analyzer::threads::{take_option, ThreadConfig};
+
+const HIGH_COVERAGE : f32 = 0.75;
+
+fn print_best(keyspace: &FourSquare, all_results: &[SimulatedAnnResult]) {
+    if let Some(best) = all_results.first() {
+        println!("Best Key:\n{}", keyspace.kind.format_key(&best.key));
+    }
+}
+
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+    let threads = ThreadConfig::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+    let segmenter = Segmenter::from_args(&mut args);
+    let criteria = StopCriteria::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+    let max_fail = take_option::<u64>(&mut args, "--max-fail").unwrap_or_else(|e| panic!("{}", e));
+    let mut kind = SquareCipher::FourSquare;
+
+    for (flag, flag_kind) in [("--twosquare", SquareCipher::TwoSquare), ("--vertical", SquareCipher::TwoSquareVertical)] {
+        if let Some(i) = args.iter().position(|a| a == flag) {
+            args.remove(i);
+            kind = flag_kind;
+        }
+    }
+
+    let fitness_spec = take_option(&mut args, "--fitness").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| String::from(solvers::default_fitness(kind.name())));
+
+    let filename = match args.first() {
+        Some(filename) => filename.clone(),
+        None => {
+            println!("Usage: foursquare FILE [--twosquare | --vertical] [--max-fail N] [--fitness SPEC] [--word-bigrams FILE]");
+            return;
+        }
+    };
+
+    let mut file = File::open(&filename).unwrap_or_else(|_| panic!("Cannot open {}", filename));
+
+    let mut text = String::new();
+
+    file.read_to_string(&mut text).unwrap();
+
+    let ngram_score = NgramScore4::create("english_quadgrams.txt");
+
+    let wl_score = WordListScore::create("wordlist.txt");
+
+    let fitness = Fitness::create(&fitness_spec, "english_bigrams.txt", "english_quadgrams.txt")
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    println!("Fitness = {}", fitness);
+
+    let keyspace = FourSquare::new(kind, CipherText::parse(&text), fitness);
+    let len = keyspace.cipher.letters.len();
+
+    if len < solvers::MIN_LETTERS {
+        println!("{} has {} letters, {} needs at least {}", filename, len, kind.name(), solvers::MIN_LETTERS);
+        std::process::exit(1);
+    }
+
+    println!("Cipher Type = {}", kind.name());
+    println!("Cipher Length = {}", len);
+
+    if len % 2 == 1 {
+        println!("Odd cipher length, the last letter is ignored");
+    }
+
+    let calibration = Calibration::from_args(&mut args, &ngram_score, len - len % 2).unwrap_or_else(|e| {
+        println!("{}", e);
+        std::process::exit(1)
+    });
+    println!("Calibration = {}", calibration);
+
+    let mut config = solvers::default_config(kind.name());
+    config.max_fail = max_fail.unwrap_or(config.max_fail);
+
+    let run = solvers::Run {
+        keyspace: Arc::new(keyspace),
+        ng_score: Arc::new(ngram_score),
+        wl_score: Arc::new(wl_score),
+        config,
+        segmenter,
+        calibration,
+        criteria,
+        high_coverage: HIGH_COVERAGE,
+        rescore: None,
+    };
+
+    let cancel = CancelToken::new();
+    cancel.cancel_on_ctrlc();
+
+    std::process::exit(run.run(&threads, &cancel, print_best));
+}
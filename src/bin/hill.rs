@@ -0,0 +1,104 @@
+extern crate analyzer;
+
+use std::fs::File;
+use std::io::Read;
+
+use analyzer::calibrate::Calibration;
+use analyzer::hill::{self, HillSolution};
+use analyzer::score::NgramScore4;
+use analyzer::text::CipherText;
+use analyzer::threads::take_option;
+
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+    let size = take_option::<usize>(&mut args, "--size").unwrap_or_else(|e| panic!("{}", e));
+    let known = take_option::<String>(&mut args, "--known").unwrap_or_else(|e| panic!("{}", e))
+        .map(|k| CipherText::parse(&k).letters);
+    let offset = take_option::<usize>(&mut args, "--offset").unwrap_or_else(|e| panic!("{}", e));
+
+    let filename = match args.first() {
+        Some(filename) => filename.clone(),
+        None => {
+            println!("Usage: hill FILE [--size N] [--known PLAIN [--offset N]] [--corpus FILE]");
+            return;
+        }
+    };
+
+    let sizes = match size {
+        Some(n) if hill::SIZES.contains(&n) => vec![n],
+        Some(n) => panic!("Size {} is not one of {:?}", n, hill::SIZES),
+        None => hill::SIZES.to_vec()
+    };
+
+    let mut text = String::new();
+
+    File::open(&filename)
+        .unwrap_or_else(|_| panic!("Cannot open {}", filename))
+        .read_to_string(&mut text)
+        .unwrap();
+
+    let cipher = CipherText::parse(&text);
+
+    let ngram_score = NgramScore4::create("english_quadgrams.txt");
+
+    println!("Cipher Length = {}", cipher.letters.len());
+
+    // Quadgram scores need at least one quadgram in the decrypt of every size
+    if cipher.letters.len() < 2 * hill::SIZES[hill::SIZES.len() - 1] {
+        println!("Cipher text is too short");
+        std::process::exit(1);
+    }
+
+    let calibration = Calibration::from_args(&mut args, &ngram_score, cipher.letters.len()).unwrap_or_else(|e| {
+        println!("{}", e);
+        std::process::exit(1)
+    });
+    println!("Calibration = {}", calibration);
+
+    let mut best : Option<HillSolution> = None;
+
+    for n in sizes {
+        if !cipher.letters.len().is_multiple_of(n) {
+            println!("Size {}: cipher length is not a multiple of {}, the last block is ignored", n, n);
+        }
+
+        let solution = match &known {
+            Some(known) => hill::crib_attack(&cipher.letters, known, n, offset, &ngram_score),
+            None => hill::ciphertext_only(&cipher.letters, n, &ngram_score)
+                .ok_or_else(|| String::from("no invertible key found"))
+        };
+
+        let solution = match solution {
+            Ok(solution) => solution,
+            Err(e) => {
+                println!("Size {}: {}", n, e);
+                continue;
+            }
+        };
+
+        let rating = calibration.rate_text(&ngram_score, &solution.plain);
+        println!("Size {}: Key = {} Score = {:.1} Rating = {}", n, solution.key.letters(), solution.score, rating);
+
+        let better = best.as_ref().is_none_or(|b| {
+            rating.z_score > calibration.rate_text(&ngram_score, &b.plain).z_score
+        });
+
+        if better {
+            best = Some(solution);
+        }
+    }
+
+    let best = match best {
+        Some(best) => best,
+        None => std::process::exit(1)
+    };
+
+    println!("Key ({}):\n{}", best.key.letters(), best.key);
+    println!("Inverse ({}):\n{}", best.inverse.letters(), best.inverse);
+
+    if best.plain.len() == cipher.letters.len() {
+        println!("Decrypt = {}", cipher.render(&best.plain));
+    } else {
+        println!("Decrypt = {}", best.plain);
+    }
+}
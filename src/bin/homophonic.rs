@@ -0,0 +1,102 @@
+extern crate analyzer;
+extern crate rand;
+
+use std::fs::File;
+use std::io::Read;
+use std::sync::Arc;
+
+use analyzer::homophonic::Homophonic;
+use analyzer::solvers;
+use analyzer::fitness::Fitness;
+use analyzer::score::{NgramScore4, WordListScore};
+use analyzer::simann::*;
+use analyzer::segment::Segmenter;
+use analyzer::stop::StopCriteria;
+use analyzer::calibrate::Calibration;
+use analyzer::cancel::CancelToken;
+use analyzer::threads::{take_option, ThreadConfig};
+
+const MAX_FAIL : u64 = 2000;
+const HIGH_COVERAGE : f32 = 0.75;
+const TEMP: i64 = 20;
+
+fn print_groups(keyspace: &Homophonic, all_results: &[SimulatedAnnResult]) {
+    if let Some(best) = all_results.first() {
+        println!("Best Homophone Groups:\n{}", keyspace.format_groups(&best.key));
+
+        if let Some(key) = keyspace.indexed_key(&best.key) {
+            println!("Best Key by Symbol = {}", key);
+        }
+    }
+}
+
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+    let threads = ThreadConfig::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+    let segmenter = Segmenter::from_args(&mut args);
+    let criteria = StopCriteria::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+    let fitness_spec = take_option(&mut args, "--fitness").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| String::from(solvers::default_fitness("homophonic")));
+
+    let filename = match args.first() {
+        Some(filename) => filename.clone(),
+        None => {
+            println!("Usage: homophonic FILE [WIDTH] [--fitness SPEC] [--word-bigrams FILE]");
+            return;
+        }
+    };
+    let width = args.get(1).map(|w| w.parse::<usize>().expect("Bad symbol width"));
+
+    let mut file = File::open(&filename).unwrap_or_else(|_| panic!("Cannot open {}", filename));
+
+    let ngram_score = NgramScore4::create("english_quadgrams.txt");
+
+    let wl_score = WordListScore::create("wordlist.txt");
+
+    let mut cipher = String::new();
+
+    file.read_to_string(&mut cipher).unwrap();
+
+    let fitness = Fitness::create(&fitness_spec, "english_bigrams.txt", "english_quadgrams.txt")
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    println!("Fitness = {}", fitness);
+
+    let keyspace = match Homophonic::new(&cipher, width, fitness) {
+        Ok(keyspace) => keyspace,
+        Err(e) => {
+            println!("{}: {}", filename, e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Cipher Length = {} Symbols = {}", keyspace.len(), keyspace.symbols.len());
+
+    let calibration = Calibration::from_args(&mut args, &ngram_score, keyspace.len()).unwrap_or_else(|e| {
+        println!("{}", e);
+        std::process::exit(1)
+    });
+    println!("Calibration = {}", calibration);
+
+    println!("Symbol Counts = {}", keyspace.symbol_counts().iter()
+        .map(|p| format!("({}, {})", p.0, p.1))
+        .collect::<Vec<_>>()
+        .join(" "));
+
+    let run = solvers::Run {
+        keyspace: Arc::new(keyspace),
+        ng_score: Arc::new(ngram_score),
+        wl_score: Arc::new(wl_score),
+        config: AnnealConfig { max_fail: MAX_FAIL, temp: TEMP },
+        segmenter,
+        calibration,
+        criteria,
+        high_coverage: HIGH_COVERAGE,
+        rescore: None,
+    };
+
+    let cancel = CancelToken::new();
+    cancel.cancel_on_ctrlc();
+
+    std::process::exit(run.run(&threads, &cancel, print_groups));
+}
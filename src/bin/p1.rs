@@ -1,222 +1,281 @@
 extern crate analyzer;
 extern crate rayon;
-extern crate thread_priority;
 
 use std::fs::File;
-use std::io::Read;
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::sync::mpsc;
-use std::time::{Duration, SystemTime};
+use std::io::{BufRead, Read, Write};
+use std::sync::Arc;
+use std::time::SystemTime;
 
-use rand::Rng;
+use rand::RngCore;
 use rand::seq::SliceRandom;
-use thread_priority::*;
+use rayon::prelude::*;
 
 use analyzer::freq::Freq;
 use analyzer::score::{NgramScore4, NgramScore2, WordListScore};
 use analyzer::simann::*;
+use analyzer::segment::Segmenter;
+use analyzer::stop::StopCriteria;
+use analyzer::calibrate::Calibration;
+use analyzer::cancel::CancelToken;
+use analyzer::threads::{take_option, ThreadConfig};
+use analyzer::text::CipherText;
+use analyzer::pattern::{self, PatternDict, PatternConfig};
+use analyzer::fitness::Fitness;
+use analyzer::keyword;
+use analyzer::session::Session;
+use analyzer::text;
+use analyzer::solvers;
+use analyzer::substitution::{self, Substitution};
 
-const NCPU : usize = 8;
 const MAX_FAIL : u64 = 80;
-const MAX_RESULTS : usize = 16;
-const PRINT_SECS : u64 = 20;
 const HIGH_COVERAGE : f32 = 0.85;
 const WORDLIST_FACTOR : f64 = 0.25;
 const TEMP: i64 = 10;
+const DEFAULT_FITNESS : &str = "blend";
+const PATTERN_MAX_NODES : u64 = 2_000_000;
+const PATTERN_MAX_SOLUTIONS : usize = 10000;
+const PATTERN_RESULTS : usize = 10;
+const KEYWORD_MAX_LEN : usize = 12;
+const KEYWORD_RESULTS : usize = 10;
+// At most 1 in PATTERN_SKIP_DIV distinct cipher words may be missing from the dictionary
+const PATTERN_SKIP_DIV : usize = 3;
+
+// Starts the annealing from a partly known key for cipher.txt
+struct SeededSubstitution(Substitution);
+
+impl KeySpace for SeededSubstitution {
+    fn generate_key(&self, rng : &mut dyn RngCore) -> String {
+        //String::from("DBGTQMCUIJKAFWPOERSXLHNVYZ")
+        //                              "X0X000000000X0000000000000";
+        let mut v : Vec<u8> = Vec::from("TNGEQBOUZRKSFIPYAWCXLHMJDV");
+        v[3..=11].shuffle(rng);
+        v[13..].shuffle(rng);
+        String::from_utf8(v).unwrap()
+    }
 
-fn generate_key<T: Rng>(rng: &mut T) -> String {
-    //String::from("DBGTQMCUIJKAFWPOERSXLHNVYZ")
-    //                              "X0X000000000X0000000000000";
-    let mut v : Vec<u8> = Vec::from("TNGEQBOUZRKSFIPYAWCXLHMJDV");
-    v[3..=11].shuffle(rng);
-    v[13..].shuffle(rng);
-    String::from_utf8(v).unwrap()
-}
-
-fn random_swap_key<T: Rng>(key : &str, rng : &mut T) -> String {
-    let mut key = String::from(key);
-    let idxs = rand::seq::index::sample(rng, key.len(), 2);
+    fn random_swap_key(&self, key : &str, rng : &mut dyn RngCore) -> String {
+        self.0.random_swap_key(key, rng)
+    }
 
-    let c1 = key.as_bytes()[idxs.index(0)];
-    let c2 = key.as_bytes()[idxs.index(1)];
+    fn decrypt(&self, key : &str) -> String {
+        self.0.decrypt(key)
+    }
 
-    unsafe {
-        key.as_bytes_mut()[idxs.index(0)] = c2;
-        key.as_bytes_mut()[idxs.index(1)] = c1;
+    fn score(&self, ng_score : &NgramScore4, wl_score : &WordListScore, text : &str) -> f64 {
+        self.0.score(ng_score, wl_score, text)
     }
 
-    key
+    fn coverage(&self, wl_score : &WordListScore, text : &str) -> f32 {
+        self.0.coverage(wl_score, text)
+    }
 }
 
-fn score_text(
-    ng_score: &NgramScore4,
-    ng2_score: &NgramScore2,
-    wl_score: &WordListScore,
-    text : &str) -> f64 {
+// Scores partly by word coverage, after the stop criteria have seen the plain score
+fn rescore(res : &mut SimulatedAnnResult) {
+    let adj_score = res.score * (1.0 - WORDLIST_FACTOR)
+        + res.score * WORDLIST_FACTOR * res.word_coverage as f64;
 
-    let score = ng_score.score(&text);
-    //let score2 = ng2_score.score(&cur_decrypt);
-    let word_coverage = wl_score.coverage(&text);
+    res.score = adj_score.round();
+}
 
-    let score = score * 0.5 + 0.5 * score * (1.0 - word_coverage) as f64;
-    //let score = score + score2;
+fn print_best(keyspace: &SeededSubstitution, all_results: &[SimulatedAnnResult]) {
+    if let Some(best) = all_results.first() {
+        println!("Best Decrypt:\n{}", keyspace.0.cipher.render(&best.decrypt));
 
-    score
+        if let Some(keyed) = keyword::analyse_key(&best.key, &[], KEYWORD_MAX_LEN).first() {
+            println!("Best Key is a Keyword Alphabet: {}", keyed);
+        }
+    }
 }
 
-fn simulated_annealing<DFn>(cipher : &str,
-    ng_score: &NgramScore4,
-    ng2_score: &NgramScore2,
-    wl_score: &WordListScore,
-    decrypt_fn: DFn) -> SimulatedAnnResult 
-    where DFn : Fn(&str, &str) -> String {
-    let mut rng = rand::thread_rng();
-
-    let mut last_key = generate_key(&mut rng);
-    let mut last_decrypt = decrypt_fn(cipher, &last_key);
-    let mut last_score = score_text(ng_score, ng2_score, wl_score, &last_decrypt);
-    let mut best_key = last_key.clone();
-    let mut best_decrypt = last_decrypt.clone();
-    let mut best_score = last_score;
-
-    for temp in 0..TEMP {
-        let mut fail_count = 0;
-
-        while fail_count < MAX_FAIL {
-            let cur_key = random_swap_key(&last_key, &mut rng);
-
-            let cur_decrypt = decrypt_fn(cipher, &cur_key);
-
-            let cur_score = score_text(ng_score, ng2_score, wl_score, &cur_decrypt);
-            if cur_score > last_score {
-                last_key = cur_key;
-                last_decrypt = cur_decrypt;
-                last_score = cur_score;
-            } else {
-                let pow = (cur_score - last_score) / (TEMP-temp) as f64;
-                let prob = pow.exp();
-
-                if prob > rng.gen() {
-                    last_key = cur_key;
-                    last_decrypt = cur_decrypt;
-                    last_score = cur_score;
-                }
-            }
+// Explains a full or partial key (? for unknown letters) as a keyword alphabet, filling in
+// the letters the keyword gives
+fn run_keyword_analysis(cipher_text : &CipherText, key : &str, dict_file : Option<&str>) {
+    let dict = dict_file.map(text::read_word_list).unwrap_or_default();
+    let results = keyword::analyse_key(key, &dict, KEYWORD_MAX_LEN);
 
-            if last_score > best_score {
-                best_key = last_key.clone();
-                best_decrypt = last_decrypt.clone();
-                best_score = last_score;
-                fail_count = 0;
-            } else {
-                fail_count += 1;
-            }
-        }
+    println!("Keyword Alphabets ({}):", results.len());
+
+    for r in results.iter().take(KEYWORD_RESULTS) {
+        println!("{}", r);
     }
 
-    let word_coverage = wl_score.coverage(&best_decrypt);
+    if let Some(best) = results.first() {
+        // Letters the key still does not give are shown as ?
+        let decrypt : String = cipher_text.letters.chars()
+            .map(|c| best.key.find(c).map(|i| ALPHABET.as_bytes()[i] as char).unwrap_or('?'))
+            .collect();
 
-    SimulatedAnnResult {
-        key: best_key,
-        decrypt: best_decrypt,
-        score: best_score,
-        word_coverage
+        println!("Decrypt with {}:\n{}", best.key, cipher_text.render(&decrypt));
     }
 }
 
-fn join_thread_run(rx_chan: mpsc::Receiver<SimulatedAnnResult>,
-    run: &Mutex<bool>) {
 
-    set_thread_priority(thread_native_id(), ThreadPriority::Max,
-        ThreadSchedulePolicy::Normal(NormalThreadSchedulePolicy::Normal)).unwrap();
+fn print_session(keyspace : &Substitution, session : &Session, ngram_score : &NgramScore4,
+    wl_score : &WordListScore) {
 
-    let mut best_heap = Vec::<SimulatedAnnResult>::new();
-    //let mut best_result : Option<SimulatedAnnResult> = None;
-    let mut last_result_counter = 0;
-    let mut result_counter = 0;
-    let mut print_time = SystemTime::now() + Duration::from_secs(PRINT_SECS);
+    let decrypt = keyspace.decrypt(session.key());
+    let locked : String = session.locked.iter()
+        .map(|l| if *l { '^' } else { ' ' })
+        .collect();
 
-    loop {
-        if *run.lock().unwrap() == false {
-            break;
-        }
-
-        let mut res = rx_chan.recv().unwrap();
-
-        result_counter += 1;
+    println!("Plain  {}\nCipher {}\nLocked {}", ALPHABET, session.key(), locked);
+    println!("Score = {:.1} Coverage = {:.2}", keyspace.score(ngram_score, wl_score, &decrypt),
+        keyspace.coverage(wl_score, &decrypt));
+    println!("{}", keyspace.cipher.render(&decrypt));
+}
 
-        if res.word_coverage > HIGH_COVERAGE {
-            println!("High Word Coverage: {}", res);
+const INTERACTIVE_HELP : &str = "Commands:
+    show                 Key, score and decrypt
+    freq                 Letter frequencies of the cipher and the decrypt
+    set C P              Cipher letter C decrypts to plain letter P
+    swap P1 P2           Swap two letters of the decrypt
+    lock LETTERS         Lock plain letters of the decrypt, unlock LETTERS or unlock all
+    anneal [N]           N annealing runs on the unlocked letters, the best is kept if it scores higher
+    undo                 Undo the last change
+    save FILE, load FILE Save or load the key and locked letters
+    quit";
+
+// Hand solving, the key starts from a saved session or English letter frequencies
+fn run_interactive(keyspace : &Substitution, ngram_score : &NgramScore4, wl_score : &WordListScore,
+    threads : &ThreadConfig, session_file : Option<&str>) {
+
+    let mut session = Session::new(&substitution::frequency_key(&keyspace.cipher.letters));
+
+    if let Some(filename) = session_file {
+        if let Err(e) = session.load(filename) {
+            println!("{}", e);
         }
+    }
 
-        let adj_score = res.score * (1.0 - WORDLIST_FACTOR)
-            + res.score * WORDLIST_FACTOR * res.word_coverage as f64;
+    let config = AnnealConfig { max_fail: MAX_FAIL, temp: TEMP };
+    let pool = threads.pool();
 
-        res.score = adj_score.round();
+    println!("{}", INTERACTIVE_HELP);
+    print_session(keyspace, &session, ngram_score, wl_score);
 
-        //if is_finished(&res.decrypt) {
-        //    best_result = Some(res);
-        //    break;
-        //}
+    let stdin = std::io::stdin();
 
-        handle_annealing_result(&mut best_heap, res);
+    loop {
+        print!("> ");
+        std::io::stdout().flush().unwrap();
 
-        if print_time < SystemTime::now() {
-            print_time = SystemTime::now() + Duration::from_secs(PRINT_SECS);
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
 
-            print_results(&best_heap);
+        let words : Vec<&str> = line.split_ascii_whitespace().collect();
+        let arg = |i : usize| words.get(i).cloned().unwrap_or("");
+        let char_arg = |i : usize| arg(i).chars().next().unwrap_or(' ');
+
+        let res = match arg(0) {
+            "" => Ok(()),
+            "show" => Ok(()),
+            "freq" => {
+                let decrypt = keyspace.decrypt(session.key());
+                println!("Cipher Freq =\n{}", Freq::from(keyspace.cipher.letters.as_str()));
+                println!("Decrypt Freq =\n{}", Freq::from(decrypt.as_str()));
+                continue;
+            },
+            "set" => session.assign(char_arg(1), char_arg(2)),
+            "swap" => session.swap(char_arg(1), char_arg(2)),
+            "lock" => session.set_locked(arg(1), true),
+            "unlock" if arg(1) == "all" => session.set_locked(ALPHABET, false),
+            "unlock" => session.set_locked(arg(1), false),
+            "anneal" => match arg(1).parse::<usize>().unwrap_or(threads.threads) {
+                0 => Err(String::from("anneal needs at least 1 run")),
+                runs => {
+                    let locked = session.keyspace(keyspace);
+                    let current = keyspace.score(ngram_score, wl_score, &keyspace.decrypt(session.key()));
+
+                    let best = pool.install(|| (0..runs).into_par_iter()
+                        .map(|_| simulated_annealing(&locked, ngram_score, wl_score, &config))
+                        .max()
+                        .unwrap());
+
+                    println!("Best of {} Runs Score = {:.1} Current Score = {:.1}", runs, best.score, current);
+
+                    if best.score > current {
+                        session.set_key(&best.key);
+                    }
 
-            println!("Current Rate {} Results/s", (result_counter - last_result_counter) as f32 / PRINT_SECS as f32);
+                    Ok(())
+                }
+            },
+            "undo" => {
+                if !session.undo() {
+                    println!("Nothing to undo");
+                }
 
-            last_result_counter = result_counter;
+                Ok(())
+            },
+            "save" => session.save(arg(1)),
+            "load" => session.load(arg(1)),
+            "quit" | "exit" => break,
+            _ => {
+                println!("{}", INTERACTIVE_HELP);
+                continue;
+            }
+        };
+
+        match res {
+            Ok(()) => print_session(keyspace, &session, ngram_score, wl_score),
+            Err(e) => println!("{}", e)
         }
     }
-
-    print_results(&best_heap);
-
-    //if let Some(best_result) = best_result {
-    //    println!("Found result: {}", best_result);
-    //}
 }
 
-fn handle_annealing_result(all_results: &mut Vec<SimulatedAnnResult>,
-    new_res: SimulatedAnnResult) {
+// Dictionary attack on the word patterns of the cipher, used instead of annealing with --pattern [DICT]
+fn run_pattern_attack(cipher_text: &CipherText, ngram_score: &NgramScore4, dict_file: &str) {
+    if !cipher_text.has_word_boundaries() {
+        println!("Pattern attack needs a cipher with word boundaries");
+        return;
+    }
 
-    match all_results.binary_search_by(|a| new_res.cmp(a)) {
-        Ok(idx) => all_results[idx] = new_res,
-        Err(idx) => all_results.insert(idx, new_res)
-    };
+    let dict = PatternDict::create(dict_file);
 
-    if all_results.len() > MAX_RESULTS {
-        all_results.resize(MAX_RESULTS, Default::default());
-    }
-}
+    let mut words : Vec<&str> = cipher_text.words().iter()
+        .map(|w| &cipher_text.letters[w.clone()])
+        .collect();
+    words.sort();
+    words.dedup();
 
-fn print_results(all_results: &Vec<SimulatedAnnResult>) {
-    println!("Periodic Results: ");
+    let config = PatternConfig {
+        max_skips: words.len() / PATTERN_SKIP_DIV,
+        max_nodes: PATTERN_MAX_NODES,
+        max_solutions: PATTERN_MAX_SOLUTIONS,
+    };
 
-    for i in all_results.iter().enumerate() {
-        println!("{}: {}\n", i.0, i.1);
-    }
+    let start = SystemTime::now();
 
-    println!("End Results");
-}
+    let results = pattern::pattern_attack(&cipher_text.letters, &words, &dict, ngram_score, &config);
 
-fn remap(cipher : &str, mapstr : &str) -> String {
-    assert_eq!(mapstr.len(), ALPHABET.len());
-    let cipher = cipher.to_uppercase();
-    let mut decrypted = String::with_capacity(cipher.len());
+    println!("Pattern Attack found {} keys in {:?}", results.len(), start.elapsed().unwrap());
 
-    for c in cipher.chars() {
-        let c_idx = mapstr.find(c).unwrap();
-        decrypted.push(ALPHABET.chars().nth(c_idx).unwrap());
+    for (i, r) in results.iter().take(PATTERN_RESULTS).enumerate() {
+        println!("{}: Key = {} Score = {} Known Letters = {}\n{}\n",
+            i, r.key, r.score, r.known, cipher_text.render(&r.decrypt));
     }
-
-    decrypted
 }
 
 fn main() {
+    let mut args : Vec<String> = std::env::args().collect();
+    let threads = ThreadConfig::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+    let segmenter = Segmenter::from_args(&mut args);
+    let criteria = StopCriteria::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+    // The dictionary is optional, the next argument unless it is another option
+    let pattern_dict = args.iter().position(|a| a == "--pattern").map(|i| {
+        args.remove(i);
+
+        match args.get(i) {
+            Some(dict) if !dict.starts_with("--") => args.remove(i),
+            _ => String::from("wordlist.txt")
+        }
+    });
+    let fitness_spec = take_option::<String>(&mut args, "--fitness").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| String::from(DEFAULT_FITNESS));
+
     let mut file = File::open("cipher.txt").expect("Cannot open cipher.txt");
 
     let ngram_score = NgramScore4::create("english_quadgrams.txt");
@@ -231,17 +290,37 @@ fn main() {
 
     file.read_to_end(&mut cipher).unwrap();
 
-    let cipher = String::from_utf8(cipher).unwrap();
-    let cipher : String = cipher.chars().filter(|c| !c.is_whitespace()).collect();
-    let cipher = cipher.to_uppercase();
-    assert_eq!(cipher.chars().filter(|c| *c < 'A' || 'Z' < *c).count(), 0);
+    let cipher_text = CipherText::parse(&String::from_utf8(cipher).unwrap());
+    let use_words = args.iter().any(|a| a == "--words");
+    let cipher = cipher_text.letters.clone();
 
     println!("Cipher = {}", cipher);
 
+    match (use_words, cipher_text.has_word_boundaries()) {
+        (true, true) => println!("Cipher has {} words, scoring word list hits on whole words", cipher_text.words().len()),
+        (true, false) => println!("Cipher has no word boundaries, ignoring --words"),
+        (false, true) => println!("Cipher has {} words, --words scores word list hits on whole words", cipher_text.words().len()),
+        (false, false) => ()
+    }
+
     println!("Cipher Freq =\n{}", Freq::from(cipher.as_str()));
 
     println!("Cipher Score = {}", ngram_score.score(&cipher));
 
+    let calibration = Calibration::from_args(&mut args, &ngram_score, cipher.len()).unwrap_or_else(|e| {
+        println!("{}", e);
+        std::process::exit(1)
+    });
+    println!("Calibration = {}", calibration);
+
+    if let Some(dict_file) = pattern_dict {
+        return run_pattern_attack(&cipher_text, &ngram_score, &dict_file);
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--keyword") {
+        let key = args.get(i + 1).expect("--keyword needs a key");
+        return run_keyword_analysis(&cipher_text, key, args.get(i + 2).map(|d| d.as_str()));
+    }
 
     //              "ETAOINSRHDLUCMFYWGPBVKXQJZ";
     //              "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
@@ -252,7 +331,7 @@ fn main() {
         assert!(remap_str.find(c).is_some());
     }
 
-    let d_cipher = remap(&cipher, remap_str);
+    let d_cipher = substitution::decrypt(&cipher, remap_str);
 
     println!("Decrypt Cipher = {}", d_cipher);
 
@@ -260,68 +339,32 @@ fn main() {
 
     println!("Decrypt Cipher Score = {}", ngram_score.score(&d_cipher));
 
-    let cipher = Arc::new(cipher);
-    let ngram_score = Arc::new(ngram_score);
-    let ngram2_score = Arc::new(ngram2_score);
-    let wl_score = Arc::new(wl_score);
-
-    let (tx_chan, rx_chan) = mpsc::sync_channel(NCPU * 4);
-
-    let run = Arc::new(Mutex::new(true));
+    let fitness = Fitness::create(&fitness_spec, "english_bigrams.txt", "english_quadgrams.txt")
+        .unwrap_or_else(|e| panic!("{}", e));
 
-    let handler_run = run.clone();
+    println!("Fitness = {}", fitness);
 
-    ctrlc::set_handler(move || {
-        let mut run = handler_run.lock().unwrap();
-        if *run {
-            println!("Signal received! Preparing to stop...");
-            *run = false;
-        } else {
-            println!("Signal received again! Dying...");
-            std::process::abort();
-        }
-    }).expect("Failed to install signal handler");
-
-
-    let join_run = run.clone();
-    let join_thread = thread::spawn(move || {
-        join_thread_run(rx_chan, &join_run);
-    });
-
-    let mut worker_threads = Vec::new();
-
-    for _ in 0..NCPU {
-        let tx_chan = tx_chan.clone();
-        let cipher = cipher.clone();
-        let ngram_score = ngram_score.clone();
-        let ngram2_score = ngram2_score.clone();
-        let wl_score = wl_score.clone();
-
-        worker_threads.push(thread::spawn(move || {
-            loop {
-                let res = simulated_annealing(
-                    &cipher,
-                    &ngram_score,
-                    &ngram2_score,
-                    &wl_score,
-                    remap);
-
-                match tx_chan.send(res) {
-                    Ok(_) => continue,
-                    Err(_) => {
-                        println!("Worker thread exiting!");
-                        return;
-                    }
-                }
-            }
-        }));
+    if let Some(i) = args.iter().position(|a| a == "--interactive") {
+        let keyspace = Substitution::new(cipher_text, fitness).with_words(use_words);
+        return run_interactive(&keyspace, &ngram_score, &wl_score, &threads, args.get(i + 1).map(|s| s.as_str()));
     }
 
-    for t in worker_threads {
-        t.join().unwrap_or_else(|_| println!("Failed to join thread"));
-    }
+    let run = solvers::Run {
+        keyspace: Arc::new(SeededSubstitution(Substitution::new(cipher_text, fitness).with_words(use_words))),
+        ng_score: Arc::new(ngram_score),
+        wl_score: Arc::new(wl_score),
+        config: AnnealConfig { max_fail: MAX_FAIL, temp: TEMP },
+        segmenter,
+        calibration,
+        criteria,
+        high_coverage: HIGH_COVERAGE,
+        rescore: Some(rescore),
+    };
+
+    let cancel = CancelToken::new();
+    cancel.cancel_on_ctrlc();
 
-    join_thread.join().unwrap_or_else(|_| println!("Join thread failed to join"));
+    std::process::exit(run.run(&threads, &cancel, print_best));
 }
 
 const ALPHABET : &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
@@ -1,174 +1,490 @@
+extern crate analyzer;
 extern crate crc32fast;
 extern crate fnv;
 extern crate rand;
 
 use std::thread;
-use std::sync::mpsc;
+use std::io::BufRead;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::collections::hash_map::Entry;
+use std::time::{Duration, Instant};
 
 use rand::Rng;
-use rand::distributions::Standard;
 use fnv::FnvHashMap;
 
-const NCONS : usize = 16;
-const NPROD_PER_CONS : usize = 4;
-const CONS_CHUNK_SZ : usize = 256;
+use analyzer::crctable::{self, CrcTable, CRC_SPACE};
+use analyzer::crcforge::{self, CrcParams};
+use analyzer::threads::{self, ThreadConfig};
 
-type CrcStr = (u32, String);
+const CAND_LEN : usize = 16;
+// CRC is affine, so candidates sharing a prefix collide with each other in large clumps or not at all
+// Collision search only needs ~2^16 hashes so it uses fully random candidates instead
+const FIND_SUFFIX_LEN : usize = 3;
+const COLLIDE_SUFFIX_LEN : usize = 0;
+const BATCH_SZ : usize = 4096;
+const SHARD_BITS : u32 = 8;
+const NSHARDS : usize = 1 << SHARD_BITS;
+const PRINT_SECS : u64 = 20;
 
-type CrcMap = FnvHashMap<u32, String>;
+// Candidates are fixed size printable strings, a random prefix followed by an enumerated suffix
+type Candidate = [u8; CAND_LEN];
 
-fn prod_thread(len: Option<usize>, tx_chan: mpsc::SyncSender<CrcStr>) {
-    let mut rng = rand::thread_rng();
+type CrcCand = (u32, Candidate);
 
-    loop {
-        let mut h = crc32fast::Hasher::new();
+type CrcMap = FnvHashMap<u32, Candidate>;
 
-        let s_len;
-        if let Some(l) = len {
-            s_len = rng.gen_range(10, std::cmp::max(11, l+4));
-        } else {
-            s_len = rng.gen_range(10, 20);
+fn cand_str(c : &[u8]) -> &str {
+    std::str::from_utf8(c).unwrap()
+}
+
+// Hash table split by the top bits of the crc so threads rarely contend on the same lock
+struct ShardedMap {
+    shards : Vec<Mutex<CrcMap>>,
+}
+
+impl ShardedMap {
+    fn new() -> Self {
+        ShardedMap {
+            shards: (0..NSHARDS).map(|_| Mutex::new(CrcMap::default())).collect()
         }
+    }
 
-        let s : String = rng.sample_iter(Standard)
-            .filter(|b| (('0' as u8 <= *b) && (*b <= 'z' as u8)))
-            .filter(|b| (*b != '\\' as u8))
-            .map(|b| b as char)
-            .take(s_len).collect();
+    fn shard(crc : u32) -> usize {
+        (crc >> (32 - SHARD_BITS)) as usize
+    }
+
+    // Sorts the batch so each shard is locked once, returns the number of new entries
+    fn insert_batch(&self, batch : &mut Vec<CrcCand>) -> Result<u64, String> {
+        batch.sort_unstable_by_key(|p| p.0);
+
+        let mut inserted = 0;
+        let mut start = 0;
+
+        while start < batch.len() {
+            let shard = ShardedMap::shard(batch[start].0);
+            let end = start + batch[start..].iter()
+                .take_while(|p| ShardedMap::shard(p.0) == shard)
+                .count();
 
-        h.update(s.as_bytes());
+            let mut map = self.shards[shard].lock().unwrap();
 
-        match tx_chan.send((h.finalize(), s)) {
-            Ok(_) => continue,
-            Err(_) => break
+            for s in &batch[start..end] {
+                if check_insert(&mut map, *s)? {
+                    inserted += 1;
+                }
+            }
+
+            start = end;
         }
+
+        batch.clear();
+
+        Ok(inserted)
+    }
+}
+
+// Counters shared by every thread, read by the stats thread
+struct Stats {
+    start : Instant,
+    worker_hashes : Vec<AtomicU64>,
+    stored : AtomicU64,
+}
+
+impl Stats {
+    fn new(nworkers : usize) -> Self {
+        Stats {
+            start: Instant::now(),
+            worker_hashes: (0..nworkers).map(|_| AtomicU64::new(0)).collect(),
+            stored: AtomicU64::new(0),
+        }
+    }
+
+    fn total_hashes(&self) -> u64 {
+        self.worker_hashes.iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .sum()
     }
+}
+
+// Probability that at least one pair among n random crcs collides
+fn collision_probability(n : u64) -> f64 {
+    let n = n as f64;
+    1.0 - (-n * (n - 1.0) / (2.0 * CRC_SPACE)).exp()
+}
 
-    println!("Producer thread exiting!");
+// Probability that at least one of n random crcs matches a fixed crc
+fn preimage_probability(n : u64) -> f64 {
+    1.0 - (-(n as f64) / CRC_SPACE).exp()
 }
 
-fn cons_thread(
-    check_collides : bool,
-    tx_chan: mpsc::SyncSender<CrcMap>,
-    rx_chan: mpsc::Receiver<CrcStr>) {
+// Expected seconds until the stored set contains a collision, mean of the birthday bound
+fn collision_expected_secs(n : u64, rate : f64) -> f64 {
+    let expected = (std::f64::consts::PI / 2.0 * CRC_SPACE).sqrt();
+    (expected - n as f64).max(0.0) / rate
+}
+
+// Preimage search is memoryless, the expected wait never shrinks
+fn preimage_expected_secs(rate : f64) -> f64 {
+    CRC_SPACE / rate
+}
+
+fn format_secs(secs : f64) -> String {
+    if !secs.is_finite() {
+        String::from("inf")
+    } else if secs < 60.0 {
+        format!("{:.1}s", secs)
+    } else if secs < 3600.0 {
+        format!("{:.1}m", secs / 60.0)
+    } else {
+        format!("{:.1}h", secs / 3600.0)
+    }
+}
+
+fn stats_thread(check_collides : bool, stats : Arc<Stats>) {
+    let mut last_worker = vec![0; stats.worker_hashes.len()];
+    let mut last_time = Instant::now();
 
     loop {
-        let mut map = CrcMap::default();
+        thread::sleep(Duration::from_secs(PRINT_SECS));
 
-        for _ in 0..CONS_CHUNK_SZ {
-            let s = rx_chan.recv().unwrap();
+        let now = Instant::now();
+        let secs = now.duration_since(last_time).as_secs_f64();
+        last_time = now;
 
-            match (check_collides, check_insert(&mut map, s)) {
-                (true, Err(s)) => {
-                    println!("Consumer Done: {}", s);
-                    return;
-                },
-                (_, _) => continue
-            }
+        println!("Periodic Stats ({} elapsed):", format_secs(stats.start.elapsed().as_secs_f64()));
+
+        let rate = threads::print_rates("Worker", "Hashes", &stats.worker_hashes, &mut last_worker, secs);
+
+        if check_collides {
+            let stored = stats.stored.load(Ordering::Relaxed);
+
+            println!("Stored Entries = {} Collision Probability = {:.4}",
+                stored, collision_probability(stored));
+            println!("Expected Remaining Time = {}",
+                format_secs(collision_expected_secs(stored, rate)));
+        } else {
+            let checked = stats.total_hashes();
+
+            println!("Checked = {} Preimage Probability = {:.4}",
+                checked, preimage_probability(checked));
+            println!("Expected Remaining Time = {}",
+                format_secs(preimage_expected_secs(rate)));
         }
 
-        match tx_chan.send(map) {
-            Ok(_) => continue,
-            Err(_) => break
+        println!("End Stats");
+    }
+}
+
+fn print_summary(check_collides : bool, stats : &Stats) {
+    let secs = stats.start.elapsed().as_secs_f64();
+    let total = stats.total_hashes();
+
+    println!("Summary:");
+    println!("Elapsed = {} Hashes = {} Average Rate = {:.0} Hashes/s",
+        format_secs(secs), total, total as f64 / secs);
+
+    if check_collides {
+        let stored = stats.stored.load(Ordering::Relaxed);
+        println!("Stored Entries = {} Collision Probability = {:.4}",
+            stored, collision_probability(stored));
+    } else {
+        println!("Checked = {} Preimage Probability = {:.4}",
+            total, preimage_probability(total));
+    }
+}
+
+// Enumerates every suffix after pos, the hasher already covers buf[..pos]
+// Returns false if the callback asked to stop
+fn hash_suffixes<F>(h : &crc32fast::Hasher, buf : &mut Candidate, pos : usize,
+    chars : &[u8], f : &mut F) -> bool
+    where F : FnMut(u32, &Candidate) -> bool {
+
+    if pos == CAND_LEN {
+        return f(h.clone().finalize(), buf);
+    }
+
+    for c in chars {
+        buf[pos] = *c;
+
+        let mut h = h.clone();
+        h.update(&buf[pos..=pos]);
+
+        if !hash_suffixes(&h, buf, pos + 1, chars, f) {
+            return false;
         }
     }
 
-    println!("Consumer thread exiting!");
+    true
 }
 
-fn find_thread(find_s: String, rx_chan: mpsc::Receiver<CrcMap>) {
-    let find_crc = {
+// Hashes batches sharing one random prefix, the prefix crc is only computed once
+fn worker_thread<F>(id : usize, suffix_len : usize, stats : &Stats, done : &AtomicBool, mut f : F)
+    where F : FnMut(u32, &Candidate) -> bool {
+    let prefix_len = CAND_LEN - suffix_len;
+    let mut rng = rand::thread_rng();
+    let chars = crctable::charset();
+    let mut buf : Candidate = [0; CAND_LEN];
+    let mut count = 0;
+
+    while !done.load(Ordering::Relaxed) {
+        for b in buf[..prefix_len].iter_mut() {
+            *b = chars[rng.gen_range(0, chars.len())];
+        }
+
         let mut h = crc32fast::Hasher::new();
-        h.update(find_s.as_bytes());
-        h.finalize()
-    };
+        h.update(&buf[..prefix_len]);
 
-    loop {
-        let mut crcs = rx_chan.recv().unwrap();
+        hash_suffixes(&h, &mut buf, prefix_len, &chars, &mut |crc, cand| {
+            count += 1;
 
-        match check_insert(&mut crcs, (find_crc, find_s.clone())) {
-            Ok(_) => continue,
-            Err(s) => {
-                println!("Join Done: {}", s);
-                return;
+            if count == BATCH_SZ {
+                stats.worker_hashes[id].fetch_add(count as u64, Ordering::Relaxed);
+                count = 0;
+
+                if done.load(Ordering::Relaxed) {
+                    return false;
+                }
             }
-        }
+
+            f(crc, cand)
+        });
     }
+
+    // The hashes since the last full batch, so the summary counts every one
+    stats.worker_hashes[id].fetch_add(count as u64, Ordering::Relaxed);
 }
 
-fn join_thread(rx_chan: mpsc::Receiver<CrcMap>) {
-    let mut all_crcs = CrcMap::default();
+fn collide_thread(id : usize, table : &ShardedMap, stats : &Stats, done : &AtomicBool) {
+    let mut batch = Vec::<CrcCand>::with_capacity(BATCH_SZ);
 
-    loop {
-        let crcs = rx_chan.recv().unwrap();
+    worker_thread(id, COLLIDE_SUFFIX_LEN, stats, done, |crc, cand| {
+        batch.push((crc, *cand));
 
-        for s in crcs {
-            match check_insert(&mut all_crcs, s) {
-                Ok(_) => continue,
-                Err(s) => {
+        if batch.len() < BATCH_SZ {
+            return true;
+        }
+
+        match table.insert_batch(&mut batch) {
+            Ok(n) => {
+                stats.stored.fetch_add(n, Ordering::Relaxed);
+                true
+            },
+            Err(s) => {
+                if !done.swap(true, Ordering::Relaxed) {
                     println!("Join Done: {}", s);
-                    return;
                 }
+                false
             }
         }
-    }
+    });
 }
 
-fn check_insert(map : &mut CrcMap, s : CrcStr) -> Result<(), String> {
+fn find_thread(id : usize, find_s : &str, stats : &Stats, done : &AtomicBool) {
+    let find_crc = crctable::crc32(find_s.as_bytes());
+
+    worker_thread(id, FIND_SUFFIX_LEN, stats, done, |crc, cand| {
+        if crc != find_crc || find_s.as_bytes() == cand {
+            return true;
+        }
+
+        if !done.swap(true, Ordering::Relaxed) {
+            println!("Join Done: Collison ({:x}) '{}' and '{}'",
+                crc, find_s, cand_str(cand));
+        }
+
+        false
+    });
+}
+
+// Returns whether the entry is new, or an error describing the collision
+fn check_insert(map : &mut CrcMap, s : CrcCand) -> Result<bool, String> {
     match (*map).entry(s.0) {
         Entry::Vacant(e) => {
             e.insert(s.1);
-            Ok(())
+            Ok(true)
         },
         Entry::Occupied(e) => {
             if *e.get() != s.1 {
-                Err(format!("Collison ({:x}) '{}' and '{}'", s.0, e.get(), s.1))
+                Err(format!("Collison ({:x}) '{}' and '{}'",
+                    s.0, cand_str(e.get()), cand_str(&s.1)))
             } else {
-                Ok(())
+                Ok(false)
             }
         }
     }
 }
 
-fn main() {
-    let q = std::env::args().nth(1);
+fn build_table(args : &[String], config : &ThreadConfig) {
+    if args.len() < 2 {
+        println!("Usage: p3 build-table FILE SUFFIX_LEN [PREFIX]");
+        return;
+    }
 
-    let (tx_map, rx_map) = mpsc::sync_channel(16);
+    let suffix_len = args[1].parse::<usize>().expect("Bad suffix length");
+    let prefix = args.get(2).map(|s| s.as_str()).unwrap_or("");
 
-    let main_thread;
-    let check_collides;
-    let len;
-    if let Some(q) = q {
-        let mut h = crc32fast::Hasher::new();
-        h.update(q.as_bytes());
-        println!("CRC32 of {} = {}", q, h.finalize());
+    let start = Instant::now();
 
-        check_collides = false;
-        len = Some(q.len());
+    config.pool().install(|| CrcTable::build(&args[0], prefix, suffix_len))
+        .unwrap_or_else(|e| panic!("Cannot write {}: {}", args[0], e));
 
-        main_thread = thread::spawn(move || { find_thread(q, rx_map); });
-    } else {
-        check_collides = true;
-        len = None;
+    let table = CrcTable::open(&args[0])
+        .unwrap_or_else(|e| panic!("Cannot open {}: {}", args[0], e));
+
+    println!("Built {} in {}", table, format_secs(start.elapsed().as_secs_f64()));
+}
 
-        main_thread = thread::spawn(move || { join_thread(rx_map); });
+// Targets are strings to collide with or crcs written as 0x hex, - reads targets from stdin
+fn query_table(args : &[String]) {
+    if args.len() < 2 {
+        println!("Usage: p3 query FILE TARGET... (TARGET is a string, 0xCRC or - for stdin)");
+        return;
     }
 
-    for _ in 0..NCONS {
-        let tx_map = tx_map.clone();
-        let (tx_str, rx_str) = mpsc::sync_channel(16);
+    let table = CrcTable::open(&args[0])
+        .unwrap_or_else(|e| panic!("Cannot open {}: {}", args[0], e));
 
-        thread::spawn(move || { cons_thread(check_collides, tx_map, rx_str); });
+    println!("{}", table);
 
-        for _ in 0..NPROD_PER_CONS {
-            let tx_str = tx_str.clone();
+    let mut targets : Vec<String> = Vec::new();
+    for a in &args[1..] {
+        if a == "-" {
+            let stdin = std::io::stdin();
+            targets.extend(stdin.lock().lines().map(|l| l.unwrap()));
+        } else {
+            targets.push(a.clone());
+        }
+    }
 
-            thread::spawn(move || { prod_thread(len, tx_str); });
+    let mut found = 0;
+    let start = Instant::now();
+
+    for t in &targets {
+        let crc = match t.strip_prefix("0x") {
+            Some(_) => parse_crc(t),
+            None => crctable::crc32(t.as_bytes())
+        };
+
+        match table.lookup(crc) {
+            Some(s) => {
+                found += 1;
+                println!("{} ({:08x}) = '{}'", t, crc, s);
+            },
+            None => println!("{} ({:08x}) = Not Found", t, crc)
         }
     }
 
-    main_thread.join().unwrap_or_else(|_| {
-        println!("Could not join main thread");
-    });
+    println!("Found {}/{} in {}", found, targets.len(),
+        format_secs(start.elapsed().as_secs_f64()));
+}
+
+fn parse_crc(s : &str) -> u32 {
+    u32::from_str_radix(s.trim_start_matches("0x"), 16)
+        .unwrap_or_else(|_| panic!("Bad crc {}", s))
+}
+
+fn forge_file(args : &[String]) {
+    if args.len() < 3 {
+        println!("Usage: p3 forge IN OUT 0xCRC [--insert OFFSET | --positions P1,P2,..] [--crc NAME]");
+        println!("Appends 4 bytes by default, NAME is crc32 crc32c bzip2 mpeg2 posix jamcrc or poly,init,refin,refout,xorout");
+        return;
+    }
+
+    let target = parse_crc(&args[2]);
+    let mut params = crcforge::CRC32;
+    let mut insert = None;
+    let mut positions = None;
+
+    let mut it = args[3..].iter();
+    while let Some(a) = it.next() {
+        let v = it.next().unwrap_or_else(|| panic!("Missing value for {}", a));
+
+        match a.as_str() {
+            "--crc" => params = CrcParams::parse(v).unwrap_or_else(|e| panic!("{}", e)),
+            "--insert" => insert = Some(v.parse::<usize>().expect("Bad offset")),
+            "--positions" => positions = Some(v.split(',')
+                .map(|p| p.parse::<usize>().expect("Bad position"))
+                .collect::<Vec<_>>()),
+            _ => panic!("Unknown option {}", a)
+        }
+    }
+
+    let mut data = std::fs::read(&args[0])
+        .unwrap_or_else(|e| panic!("Cannot open {}: {}", args[0], e));
+
+    println!("{} ({} bytes) crc = {:08x}", args[0], data.len(), params.checksum(&data));
+
+    let res = match (insert, positions) {
+        (Some(offset), None) => crcforge::forge_insert(&params, &mut data, offset, target),
+        (None, Some(positions)) => crcforge::forge(&params, &mut data, &positions, target),
+        (None, None) => crcforge::forge_append(&params, &mut data, target),
+        (Some(_), Some(_)) => Err(String::from("Only one of --insert and --positions can be given"))
+    };
+
+    if let Err(e) = res {
+        println!("Forge failed: {}", e);
+        return;
+    }
+
+    std::fs::write(&args[1], &data)
+        .unwrap_or_else(|e| panic!("Cannot write {}: {}", args[1], e));
+
+    println!("{} ({} bytes) crc = {:08x}", args[1], data.len(), params.checksum(&data));
+}
+
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+    let config = ThreadConfig::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+
+    match args.first().map(|s| s.as_str()) {
+        Some("build-table") => return build_table(&args[1..], &config),
+        Some("query") => return query_table(&args[1..]),
+        Some("forge") => return forge_file(&args[1..]),
+        _ => ()
+    }
+
+    let q = args.first().cloned();
+
+    println!("Threads = {}", config);
+
+    let stats = Arc::new(Stats::new(config.threads));
+    let done = Arc::new(AtomicBool::new(false));
+    let table = Arc::new(ShardedMap::new());
+
+    let check_collides = q.is_none();
+
+    if let Some(q) = &q {
+        println!("CRC32 of {} = {}", q, crctable::crc32(q.as_bytes()));
+    }
+
+    {
+        let stats = stats.clone();
+        thread::spawn(move || { stats_thread(check_collides, stats); });
+    }
+
+    let mut worker_threads = Vec::new();
+
+    for id in 0..config.threads {
+        let config = config.clone();
+        let q = q.clone();
+        let stats = stats.clone();
+        let done = done.clone();
+        let table = table.clone();
+
+        worker_threads.push(thread::spawn(move || {
+            config.setup_worker(id);
+
+            match q {
+                Some(q) => find_thread(id, &q, &stats, &done),
+                None => collide_thread(id, &table, &stats, &done)
+            }
+        }));
+    }
+
+    for t in worker_threads {
+        t.join().unwrap_or_else(|_| println!("Failed to join thread"));
+    }
+
+    print_summary(check_collides, &stats);
 }
@@ -0,0 +1,404 @@
+extern crate analyzer;
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use analyzer::calibrate::Calibration;
+use analyzer::cancel::CancelToken;
+use analyzer::threads::ThreadConfig;
+use analyzer::freq::Freq;
+use analyzer::score::{NgramScore4, WordListScore};
+use analyzer::segment::Segmenter;
+use analyzer::simann::*;
+use analyzer::solvers::{self, BoxKeySpace};
+
+const DEFAULT_PORT : u16 = 8442;
+const MAX_RESULTS : usize = 16;
+const RATE_SECS : u64 = 5;
+const EVENT_SECS : u64 = 1;
+const MAX_BODY : usize = 1 << 20;
+
+const DASHBOARD : &str = include_str!("dashboard.html");
+
+struct Leaderboard {
+    best : Vec<SimulatedAnnResult>,
+    results : u64,
+    rate : f32,
+}
+
+struct Job {
+    id : usize,
+    cipher : String,
+    fitness : String,
+    text : String,
+    keyspace : BoxKeySpace,
+    // The flag the threads of the current run check, None while stopped
+    run : Mutex<Option<CancelToken>>,
+    leaderboard : Mutex<Leaderboard>,
+}
+
+// The tables every job shares
+struct Scores {
+    ngram : Arc<NgramScore4>,
+    wl : Arc<WordListScore>,
+    segmenter : Arc<Segmenter>,
+}
+
+struct Server {
+    scores : Scores,
+    threads : ThreadConfig,
+    jobs : Mutex<Vec<Arc<Job>>>,
+}
+
+struct Request {
+    method : String,
+    path : Vec<String>,
+    query : HashMap<String, String>,
+    body : String,
+}
+
+fn json_string(s : &str) -> String {
+    let mut out = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+fn freq_json(text : &str) -> String {
+    let freq = Freq::from(text.to_uppercase().as_str());
+    let counts : Vec<String> = freq.freqs.iter().map(|p| format!("[\"{}\",{}]", p.0, p.1)).collect();
+
+    format!("{{\"freqs\":[{}],\"score\":{}}}", counts.join(","), freq.score)
+}
+
+fn result_json(res : &SimulatedAnnResult) -> String {
+    let rating = res.rating
+        .map(|r| format!("{{\"z_score\":{},\"english_like\":{}}}", r.z_score, r.english_like))
+        .unwrap_or_else(|| String::from("null"));
+
+    format!("{{\"key\":{},\"decrypt\":{},\"score\":{},\"word_coverage\":{},\"rating\":{},\"segmented\":{},\"segment_score\":{}}}",
+        json_string(&res.key), json_string(&res.decrypt), res.score, res.word_coverage, rating,
+        json_string(&res.segmented), res.segment_score)
+}
+
+// The job summary, with the leaderboard and Freq tables when full
+fn job_json(job : &Job, full : bool) -> String {
+    let board = job.leaderboard.lock().unwrap();
+    let mut out = format!("{{\"id\":{},\"cipher\":{},\"fitness\":{},\"running\":{},\"results\":{},\"rate\":{},\"best_score\":{}",
+        job.id, json_string(&job.cipher), json_string(&job.fitness), job.run.lock().unwrap().is_some(),
+        board.results, board.rate, board.best.first().map(|b| b.score.to_string()).unwrap_or_else(|| String::from("null")));
+
+    if full {
+        let results : Vec<String> = board.best.iter().map(result_json).collect();
+
+        out.push_str(&format!(",\"text\":{},\"best\":[{}],\"cipher_freq\":{},\"decrypt_freq\":{}",
+            json_string(&job.text), results.join(","), freq_json(&job.text),
+            freq_json(board.best.first().map(|b| b.decrypt.as_str()).unwrap_or(""))));
+    }
+
+    out.push('}');
+    out
+}
+
+// Anneals on every worker thread until the job is stopped, the collector keeps the leaderboard
+fn start_job(job : &Arc<Job>, server : &Server) {
+    let scores = &server.scores;
+    let mut job_run = job.run.lock().unwrap();
+    if job_run.is_some() {
+        return;
+    }
+
+    let cancel = CancelToken::new();
+    *job_run = Some(cancel.clone());
+    drop(job_run);
+
+    let (rx_chan, _workers) = solvers::spawn_workers(&job.keyspace, &scores.ngram, &scores.wl,
+        &solvers::default_config(&job.cipher), &server.threads, &cancel);
+
+    let job = job.clone();
+    let segmenter = scores.segmenter.clone();
+    let ngram_score = scores.ngram.clone();
+
+    thread::spawn(move || {
+        let mut rate_time = Instant::now();
+        let mut last_result_counter = 0;
+        let mut calibration = None;
+
+        while !cancel.is_cancelled() {
+            match rx_chan.recv_timeout(Duration::from_secs(EVENT_SECS)) {
+                Ok(mut res) => {
+                    res.segment(&segmenter);
+                    res.rate(calibration.get_or_insert_with(|| Calibration::model(&ngram_score, res.decrypt.len())),
+                        &ngram_score);
+
+                    let mut board = job.leaderboard.lock().unwrap();
+                    board.results += 1;
+                    handle_annealing_result(&mut board.best, res, MAX_RESULTS);
+                },
+                Err(mpsc::RecvTimeoutError::Timeout) => (),
+                // Every worker has panicked
+                Err(mpsc::RecvTimeoutError::Disconnected) => break
+            }
+
+            if rate_time.elapsed() >= Duration::from_secs(RATE_SECS) {
+                let mut board = job.leaderboard.lock().unwrap();
+                board.rate = (board.results - last_result_counter) as f32 / rate_time.elapsed().as_secs_f32();
+                last_result_counter = board.results;
+                rate_time = Instant::now();
+            }
+        }
+
+        // A stop followed by a start hands the job to a new run, whose rate this one must leave alone
+        let mut job_run = job.run.lock().unwrap();
+        let current = match job_run.as_ref() {
+            Some(run) if run.same(&cancel) => {
+                *job_run = None;
+                true
+            },
+            Some(_) => false,
+            None => true
+        };
+        drop(job_run);
+
+        if current {
+            job.leaderboard.lock().unwrap().rate = 0.0;
+        }
+    });
+}
+
+fn percent_decode(s : &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()
+                    .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+                match hex {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    },
+                    None => out.push(b'%')
+                }
+            },
+            b => out.push(b)
+        }
+
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Reads the request line, the headers and a Content-Length body
+fn read_request(reader : &mut BufReader<TcpStream>) -> Result<Request, String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or("Empty request")?.to_string();
+    let target = parts.next().ok_or("No request target")?;
+
+    let (path, query) = match target.find('?') {
+        Some(i) => (&target[..i], &target[i + 1..]),
+        None => (target, "")
+    };
+
+    let path = path.split('/').filter(|p| !p.is_empty()).map(percent_decode).collect();
+    let query = query.split('&')
+        .filter(|q| !q.is_empty())
+        .map(|q| match q.find('=') {
+            Some(i) => (percent_decode(&q[..i]), percent_decode(&q[i + 1..])),
+            None => (percent_decode(q), String::new())
+        })
+        .collect();
+
+    let mut length = 0;
+
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).map_err(|e| e.to_string())?;
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(i) = header.find(':') {
+            if header[..i].eq_ignore_ascii_case("content-length") {
+                length = header[i + 1..].trim().parse().map_err(|_| "Bad Content-Length")?;
+            }
+        }
+    }
+
+    if length > MAX_BODY {
+        return Err(String::from("Body too large"));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+
+    Ok(Request { method, path, query, body: String::from_utf8_lossy(&body).into_owned() })
+}
+
+fn respond(stream : &mut TcpStream, status : &str, content_type : &str, body : &str) {
+    let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body);
+}
+
+fn respond_json(stream : &mut TcpStream, body : &str) {
+    respond(stream, "200 OK", "application/json", body);
+}
+
+fn respond_error(stream : &mut TcpStream, status : &str, message : &str) {
+    respond(stream, status, "application/json", &format!("{{\"error\":{}}}", json_string(message)));
+}
+
+// Sends the job with its leaderboard every EVENT_SECS until the client goes away
+fn stream_events(stream : &mut TcpStream, job : &Job) {
+    if write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+        Connection: close\r\n\r\n").is_err() {
+        return;
+    }
+
+    while write!(stream, "data: {}\n\n", job_json(job, true)).and_then(|_| stream.flush()).is_ok() {
+        thread::sleep(Duration::from_secs(EVENT_SECS));
+    }
+}
+
+fn find_job(server : &Server, id : &str) -> Option<Arc<Job>> {
+    let id = id.parse::<usize>().ok()?;
+    server.jobs.lock().unwrap().iter().find(|j| j.id == id).cloned()
+}
+
+fn handle(server : &Server, mut stream : TcpStream) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    let req = match read_request(&mut reader) {
+        Ok(req) => req,
+        Err(e) => return respond_error(&mut stream, "400 Bad Request", &e)
+    };
+
+    let path : Vec<&str> = req.path.iter().map(|p| p.as_str()).collect();
+
+    match (req.method.as_str(), path.as_slice()) {
+        ("GET", []) => respond(&mut stream, "200 OK", "text/html; charset=utf-8", DASHBOARD),
+        ("GET", ["jobs"]) => {
+            let jobs : Vec<String> = server.jobs.lock().unwrap().iter().map(|j| job_json(j, false)).collect();
+            respond_json(&mut stream, &format!("[{}]", jobs.join(",")));
+        },
+        ("POST", ["jobs"]) => {
+            let cipher = req.query.get("cipher").map(|c| c.as_str()).unwrap_or("substitution");
+            let fitness = req.query.get("fitness").map(|f| f.as_str()).filter(|f| !f.is_empty());
+
+            match solvers::keyspace(cipher, &req.body, fitness) {
+                Ok(keyspace) => {
+                    let mut jobs = server.jobs.lock().unwrap();
+                    let job = Arc::new(Job {
+                        id: jobs.len() + 1,
+                        cipher: String::from(cipher),
+                        fitness: String::from(fitness.unwrap_or_else(|| solvers::default_fitness(cipher))),
+                        text: String::from(req.body.trim()),
+                        keyspace,
+                        run: Mutex::new(None),
+                        leaderboard: Mutex::new(Leaderboard { best: Vec::new(), results: 0, rate: 0.0 }),
+                    });
+
+                    println!("Job {} started: {} with fitness {}", job.id, job.cipher, job.fitness);
+
+                    start_job(&job, server);
+                    jobs.push(job.clone());
+                    drop(jobs);
+
+                    respond_json(&mut stream, &job_json(&job, false));
+                },
+                Err(e) => respond_error(&mut stream, "400 Bad Request", &e)
+            }
+        },
+        (method, ["jobs", id, rest @ ..]) => {
+            let job = match find_job(server, id) {
+                Some(job) => job,
+                None => return respond_error(&mut stream, "404 Not Found", "No such job")
+            };
+
+            match (method, rest) {
+                ("GET", []) => respond_json(&mut stream, &job_json(&job, true)),
+                ("GET", ["events"]) => stream_events(&mut stream, &job),
+                ("POST", ["stop"]) => {
+                    if let Some(cancel) = job.run.lock().unwrap().take() {
+                        cancel.cancel();
+                    }
+
+                    println!("Job {} stopped", job.id);
+                    respond_json(&mut stream, &job_json(&job, false));
+                },
+                ("POST", ["start"]) => {
+                    start_job(&job, server);
+                    println!("Job {} restarted", job.id);
+                    respond_json(&mut stream, &job_json(&job, false));
+                },
+                _ => respond_error(&mut stream, "404 Not Found", "No such route")
+            }
+        },
+        _ => respond_error(&mut stream, "404 Not Found", "No such route")
+    }
+}
+
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+    let threads = ThreadConfig::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+    let segmenter = Segmenter::from_args(&mut args);
+
+    let port = args.first()
+        .map(|p| p.parse::<u16>().expect("Bad port"))
+        .unwrap_or(DEFAULT_PORT);
+
+    println!("Threads = {} per job", threads);
+
+    let server = Arc::new(Server {
+        scores: Scores {
+            ngram: Arc::new(NgramScore4::create("english_quadgrams.txt")),
+            wl: Arc::new(WordListScore::create("wordlist.txt")),
+            segmenter: Arc::new(segmenter),
+        },
+        threads,
+        jobs: Mutex::new(Vec::new()),
+    });
+
+    // Only local clients, jobs use every core and take any text
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|e| panic!("Cannot bind port {}: {}", port, e));
+
+    println!("Dashboard on http://127.0.0.1:{}/", port);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let server = server.clone();
+                thread::spawn(move || handle(&server, stream));
+            },
+            Err(e) => println!("Connection failed: {}", e)
+        }
+    }
+}
@@ -0,0 +1,135 @@
+extern crate analyzer;
+
+use std::io::BufReader;
+use std::net::{Shutdown, TcpStream};
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use analyzer::cancel::CancelToken;
+use analyzer::distrib::{self, JobSpec, Message, HEARTBEAT_SECS, DEAD_SECS};
+use analyzer::score::{NgramScore4, WordListScore};
+use analyzer::solvers;
+use analyzer::threads::{take_option, ThreadConfig};
+
+const RECONNECT_SECS : u64 = 5;
+
+// Anneals the job until it is cancelled, sending each restart to the coordinator with heartbeats
+// in between
+fn run_job(stream : TcpStream, job : JobSpec, threads : &ThreadConfig, ng_score : &Arc<NgramScore4>,
+    wl_score : &Arc<WordListScore>, cancel : &CancelToken) -> Result<(), String> {
+
+    let keyspace = solvers::keyspace(&job.cipher, &job.text, Some(&job.fitness))?;
+
+    println!("Job {}: {} with fitness {} on {}", job.id, job.cipher, job.fitness, threads);
+
+    let (rx_chan, workers) = solvers::spawn_workers(&keyspace, ng_score, wl_score, &job.config(), threads, cancel);
+
+    let mut stream = stream;
+    let mut results = 0;
+
+    while !cancel.is_cancelled() {
+        let msg = match rx_chan.recv_timeout(Duration::from_secs(HEARTBEAT_SECS)) {
+            Ok(res) => {
+                results += 1;
+                Message::Result { job: job.id, key: res.key }
+            },
+            Err(mpsc::RecvTimeoutError::Timeout) => Message::Heartbeat,
+            // The workers only stop early by panicking
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                let panicked = workers.join();
+                return Err(format!("{} of {} worker threads panicked after {} results", panicked, threads.threads, results));
+            }
+        };
+
+        if let Err(e) = distrib::write_message(&mut stream, &msg) {
+            return Err(format!("Lost coordinator after {} results: {}", results, e));
+        }
+    }
+
+    Ok(())
+}
+
+// Registers with the coordinator and works on the job it hands out until the connection drops
+fn session(addr : &str, name : &str, threads : &ThreadConfig, ng_score : &Arc<NgramScore4>,
+    wl_score : &Arc<WordListScore>) -> Result<(), String> {
+
+    let mut stream = TcpStream::connect(addr).map_err(|e| format!("Cannot connect to {}: {}", addr, e))?;
+    stream.set_read_timeout(Some(Duration::from_secs(DEAD_SECS))).map_err(|e| e.to_string())?;
+
+    distrib::write_message(&mut stream, &Message::Hello { name: String::from(name), threads: threads.threads })
+        .map_err(|e| e.to_string())?;
+
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+
+    let job = match distrib::read_message(&mut reader)? {
+        Message::Job(job) => job,
+        _ => return Err(String::from("Expected a job"))
+    };
+
+    let cancel = CancelToken::new();
+    let writer_cancel = cancel.clone();
+    let ngram_score = ng_score.clone();
+    let wl_score = wl_score.clone();
+
+    let threads = threads.clone();
+    let shutdown_stream = stream.try_clone().map_err(|e| e.to_string())?;
+
+    // Whatever stops the job also closes the connection, so the read below returns
+    let writer = thread::spawn(move || {
+        let res = run_job(stream, job, &threads, &ngram_score, &wl_score, &writer_cancel);
+        writer_cancel.cancel();
+        let _ = shutdown_stream.shutdown(Shutdown::Both);
+        res
+    });
+
+    // The coordinator sends heartbeats, a read timeout means it is gone
+    let read_result = loop {
+        if cancel.is_cancelled() {
+            break Ok(());
+        }
+
+        match distrib::read_message(&mut reader) {
+            Ok(Message::Heartbeat) => continue,
+            Ok(_) => break Err(String::from("Unexpected message")),
+            Err(e) => break Err(format!("Lost coordinator: {}", e))
+        }
+    };
+
+    cancel.cancel();
+
+    writer.join().unwrap_or_else(|_| Err(String::from("Worker thread panicked")))?;
+    read_result
+}
+
+fn main() {
+    let mut args : Vec<String> = std::env::args().skip(1).collect();
+
+    let threads = ThreadConfig::from_args(&mut args).unwrap_or_else(|e| panic!("{}", e));
+
+    let name = take_option(&mut args, "--name").unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| format!("worker-{}", std::process::id()));
+
+    let addr = match args.first() {
+        Some(addr) if addr.contains(':') => addr.clone(),
+        Some(host) => format!("{}:{}", host, distrib::DEFAULT_PORT),
+        None => {
+            println!("Usage: worker HOST[:PORT] [--threads N] [--pin] [--nice] [--name NAME]");
+            return;
+        }
+    };
+
+    let ngram_score = Arc::new(NgramScore4::create("english_quadgrams.txt"));
+    let wl_score = Arc::new(WordListScore::create("wordlist.txt"));
+
+    loop {
+        match session(&addr, &name, &threads, &ngram_score, &wl_score) {
+            Ok(()) => println!("Job finished"),
+            Err(e) => println!("{}", e)
+        }
+
+        println!("Reconnecting in {}s", RECONNECT_SECS);
+        thread::sleep(Duration::from_secs(RECONNECT_SECS));
+    }
+}
@@ -0,0 +1,69 @@
+extern crate ctrlc;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Exit status of the solvers, 130 is what shells report for a process stopped by SIGINT
+pub const EXIT_OK : i32 = 0;
+pub const EXIT_FAILED : i32 = 1;
+pub const EXIT_INTERRUPTED : i32 = 130;
+
+#[derive(Default)]
+struct State {
+    cancelled : AtomicBool,
+    interrupted : AtomicBool,
+}
+
+// Asks every thread of a run to stop at its next check, the annealing checks it once per move
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<State>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Whether both are clones of the same token
+    pub fn same(&self, other : &CancelToken) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    // Whether Ctrl-C rather than the run itself cancelled the token
+    pub fn was_interrupted(&self) -> bool {
+        self.0.interrupted.load(Ordering::SeqCst)
+    }
+
+    // The exit status of a run that has finished, failed if any of its threads panicked
+    pub fn exit_status(&self, failed : bool) -> i32 {
+        if self.was_interrupted() {
+            EXIT_INTERRUPTED
+        } else if failed {
+            EXIT_FAILED
+        } else {
+            EXIT_OK
+        }
+    }
+
+    // The first Ctrl-C cancels the token, a second exits at once with EXIT_INTERRUPTED
+    pub fn cancel_on_ctrlc(&self) {
+        let token = self.clone();
+
+        ctrlc::set_handler(move || {
+            if token.0.interrupted.swap(true, Ordering::SeqCst) {
+                println!("Signal received again! Exiting without waiting for workers");
+                std::process::exit(EXIT_INTERRUPTED);
+            }
+
+            println!("Signal received! Stopping workers...");
+            token.cancel();
+        }).expect("Failed to install signal handler");
+    }
+}
@@ -0,0 +1,305 @@
+extern crate crc32fast;
+
+// Largest number of byte positions forge will solve over, one bit per position bit in a u128
+pub const MAX_POSITIONS : usize = 16;
+
+// Rocksoft model parameters of a 32 bit crc
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CrcParams {
+    pub poly : u32,
+    pub init : u32,
+    pub refin : bool,
+    pub refout : bool,
+    pub xorout : u32,
+}
+
+pub const CRC32 : CrcParams = CrcParams {
+    poly: 0x04C1_1DB7, init: 0xFFFF_FFFF, refin: true, refout: true, xorout: 0xFFFF_FFFF
+};
+
+pub const CRC32C : CrcParams = CrcParams {
+    poly: 0x1EDC_6F41, init: 0xFFFF_FFFF, refin: true, refout: true, xorout: 0xFFFF_FFFF
+};
+
+pub const CRC32_BZIP2 : CrcParams = CrcParams {
+    poly: 0x04C1_1DB7, init: 0xFFFF_FFFF, refin: false, refout: false, xorout: 0xFFFF_FFFF
+};
+
+pub const CRC32_MPEG2 : CrcParams = CrcParams {
+    poly: 0x04C1_1DB7, init: 0xFFFF_FFFF, refin: false, refout: false, xorout: 0
+};
+
+pub const CRC32_POSIX : CrcParams = CrcParams {
+    poly: 0x04C1_1DB7, init: 0, refin: false, refout: false, xorout: 0xFFFF_FFFF
+};
+
+pub const CRC32_JAMCRC : CrcParams = CrcParams {
+    poly: 0x04C1_1DB7, init: 0xFFFF_FFFF, refin: true, refout: true, xorout: 0
+};
+
+const NAMED : [(&str, CrcParams); 6] = [
+    ("crc32", CRC32),
+    ("crc32c", CRC32C),
+    ("bzip2", CRC32_BZIP2),
+    ("mpeg2", CRC32_MPEG2),
+    ("posix", CRC32_POSIX),
+    ("jamcrc", CRC32_JAMCRC),
+];
+
+impl CrcParams {
+    // Either a name from NAMED or poly,init,refin,refout,xorout with the numbers in hex
+    pub fn parse(s : &str) -> Result<Self, String> {
+        if let Some(p) = NAMED.iter().find(|p| p.0 == s) {
+            return Ok(p.1);
+        }
+
+        let parts : Vec<&str> = s.split(',').collect();
+        if parts.len() != 5 {
+            return Err(format!("Unknown crc '{}', expected one of {} or poly,init,refin,refout,xorout",
+                s, NAMED.iter().map(|p| p.0).collect::<Vec<_>>().join(" ")));
+        }
+
+        let hex = |v : &str| u32::from_str_radix(v.trim_start_matches("0x"), 16)
+            .map_err(|e| format!("Bad crc parameter '{}': {}", v, e));
+        let flag = |v : &str| v.parse::<bool>()
+            .map_err(|e| format!("Bad crc parameter '{}': {}", v, e));
+
+        Ok(CrcParams {
+            poly: hex(parts[0])?,
+            init: hex(parts[1])?,
+            refin: flag(parts[2])?,
+            refout: flag(parts[3])?,
+            xorout: hex(parts[4])?,
+        })
+    }
+
+    fn table(&self) -> [u32; 256] {
+        let mut table = [0; 256];
+
+        for (i, t) in table.iter_mut().enumerate() {
+            let mut reg = (i as u32) << 24;
+
+            for _ in 0..8 {
+                reg = if reg & 0x8000_0000 != 0 { (reg << 1) ^ self.poly } else { reg << 1 };
+            }
+
+            *t = reg;
+        }
+
+        table
+    }
+
+    pub fn checksum(&self, data : &[u8]) -> u32 {
+        self.checksum_with(&self.table(), data)
+    }
+
+    fn checksum_with(&self, table : &[u32; 256], data : &[u8]) -> u32 {
+        let mut reg = self.init;
+
+        for b in data {
+            let b = if self.refin { b.reverse_bits() } else { *b };
+            reg = (reg << 8) ^ table[((reg >> 24) as u8 ^ b) as usize];
+        }
+
+        self.output(reg) ^ self.xorout
+    }
+
+    fn output(&self, reg : u32) -> u32 {
+        if self.refout { reg.reverse_bits() } else { reg }
+    }
+}
+
+// GF(2) linear map of the crc register, the image of each register bit
+type RegisterMap = [u32; 32];
+
+fn apply_map(map : &RegisterMap, reg : u32) -> u32 {
+    (0..32).filter(|i| reg & (1 << i) != 0).fold(0, |acc, i| acc ^ map[i])
+}
+
+// Runs a register through any number of zero bytes, from the maps for 1, 2, 4 ... zero bytes
+struct ZeroShift {
+    powers : Vec<RegisterMap>,
+}
+
+impl ZeroShift {
+    fn new(table : &[u32; 256], max_len : usize) -> Self {
+        let mut one = [0; 32];
+
+        for (i, m) in one.iter_mut().enumerate() {
+            let reg = 1u32 << i;
+            *m = (reg << 8) ^ table[(reg >> 24) as usize];
+        }
+
+        let mut powers = vec![one];
+
+        while (1 << powers.len()) <= max_len {
+            let last = powers[powers.len() - 1];
+            let mut square = [0; 32];
+
+            for (i, m) in square.iter_mut().enumerate() {
+                *m = apply_map(&last, last[i]);
+            }
+
+            powers.push(square);
+        }
+
+        ZeroShift { powers }
+    }
+
+    fn shift(&self, mut reg : u32, zeros : usize) -> u32 {
+        for (k, map) in self.powers.iter().enumerate() {
+            if zeros & (1 << k) != 0 {
+                reg = apply_map(map, reg);
+            }
+        }
+
+        reg
+    }
+}
+
+// Flips bits of data at the given byte positions so the crc of data becomes target
+// The crc is affine in the message bits, so this solves a 32 row linear system over GF(2)
+pub fn forge(params : &CrcParams, data : &mut [u8], positions : &[usize], target : u32) -> Result<(), String> {
+    if positions.is_empty() || positions.len() > MAX_POSITIONS {
+        return Err(format!("Expected 1 to {} positions", MAX_POSITIONS));
+    }
+
+    if let Some(p) = positions.iter().find(|p| **p >= data.len()) {
+        return Err(format!("Position {} is past the end of the data ({} bytes)", p, data.len()));
+    }
+
+    let table = params.table();
+    let base = params.checksum_with(&table, data);
+    let zeros = ZeroShift::new(&table, data.len());
+
+    // Change in crc caused by flipping each free bit, the init and xorout cancel so it is the
+    // flipped byte run from a zero register and through the zero bytes after it
+    let mut cols = Vec::with_capacity(positions.len() * 8);
+    for p in positions {
+        for bit in 0..8 {
+            let b = if params.refin { 1u8 << (7 - bit) } else { 1 << bit };
+            let reg = zeros.shift(table[b as usize], data.len() - 1 - p);
+            cols.push(params.output(reg));
+        }
+    }
+
+    // basis[b] holds a reduced column with highest bit b and the free bits that produce it
+    let mut basis : [Option<(u32, u128)>; 32] = [None; 32];
+
+    let reduce = |basis : &[Option<(u32, u128)>; 32], mut v : u32, mut mask : u128| {
+        while v != 0 {
+            let b = 31 - v.leading_zeros() as usize;
+            match basis[b] {
+                Some((bv, bm)) => {
+                    v ^= bv;
+                    mask ^= bm;
+                },
+                None => break
+            }
+        }
+        (v, mask)
+    };
+
+    for (j, c) in cols.iter().enumerate() {
+        let (v, mask) = reduce(&basis, *c, 1 << j);
+
+        if v != 0 {
+            basis[31 - v.leading_zeros() as usize] = Some((v, mask));
+        }
+    }
+
+    let (rem, mask) = reduce(&basis, base ^ target, 0);
+
+    if rem != 0 {
+        return Err(format!("Positions {:?} cannot reach crc {:08x}", positions, target));
+    }
+
+    for j in 0..cols.len() {
+        if mask & (1 << j) != 0 {
+            data[positions[j / 8]] ^= 1 << (j % 8);
+        }
+    }
+
+    verify(params, data, target)
+}
+
+// Appends 4 bytes to data that give the target crc
+pub fn forge_append(params : &CrcParams, data : &mut Vec<u8>, target : u32) -> Result<(), String> {
+    let len = data.len();
+    data.extend_from_slice(&[0; 4]);
+    forge(params, data, &[len, len + 1, len + 2, len + 3], target)
+}
+
+// Inserts 4 bytes before offset that give the target crc
+pub fn forge_insert(params : &CrcParams, data : &mut Vec<u8>, offset : usize, target : u32) -> Result<(), String> {
+    if offset > data.len() {
+        return Err(format!("Offset {} is past the end of the data ({} bytes)", offset, data.len()));
+    }
+
+    data.splice(offset..offset, [0; 4].iter().cloned());
+    forge(params, data, &[offset, offset + 1, offset + 2, offset + 3], target)
+}
+
+// Checks the forged data, crc32fast is used as an independent check of the standard crc
+fn verify(params : &CrcParams, data : &[u8], target : u32) -> Result<(), String> {
+    let crc = if *params == CRC32 {
+        let mut h = crc32fast::Hasher::new();
+        h.update(data);
+        h.finalize()
+    } else {
+        params.checksum(data)
+    };
+
+    if crc == target {
+        Ok(())
+    } else {
+        Err(format!("Forged crc {:08x} does not match target {:08x}", crc, target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA : &[u8] = b"The quick brown fox jumps over the lazy dog\n";
+    const TARGET : u32 = 0xDEAD_BEEF;
+
+    #[test]
+    fn checksums_of_the_check_string() {
+        let check = [(CRC32, 0xCBF4_3926), (CRC32C, 0xE306_9283), (CRC32_BZIP2, 0xFC89_1918),
+            (CRC32_MPEG2, 0x0376_E6E7), (CRC32_POSIX, 0x765E_7680), (CRC32_JAMCRC, 0x340B_C6D9)];
+
+        for (params, crc) in check.iter() {
+            assert_eq!(params.checksum(b"123456789"), *crc);
+        }
+    }
+
+    #[test]
+    fn forged_data_matches_crc32fast() {
+        let mut appended = DATA.to_vec();
+        forge_append(&CRC32, &mut appended, TARGET).unwrap();
+        assert_eq!(&appended[..DATA.len()], DATA);
+        assert_eq!(crc32fast::hash(&appended), TARGET);
+
+        let mut inserted = DATA.to_vec();
+        forge_insert(&CRC32, &mut inserted, 10, TARGET).unwrap();
+        assert_eq!(inserted.len(), DATA.len() + 4);
+        assert_eq!(crc32fast::hash(&inserted), TARGET);
+
+        // Scattered bytes, more than the 4 needed
+        let mut flipped = DATA.to_vec();
+        forge(&CRC32, &mut flipped, &[0, 7, 13, 20, 31, 40], TARGET).unwrap();
+        assert_eq!(crc32fast::hash(&flipped), TARGET);
+    }
+
+    #[test]
+    fn forge_errors() {
+        let mut data = DATA.to_vec();
+        assert!(forge(&CRC32, &mut data, &[DATA.len()], TARGET).is_err());
+        assert!(forge(&CRC32, &mut data, &[], TARGET).is_err());
+
+        // One byte has 8 bits to change 32
+        assert!(forge(&CRC32, &mut data, &[3], TARGET).is_err());
+        assert_eq!(data, DATA);
+    }
+}
@@ -0,0 +1,254 @@
+extern crate crc32fast;
+extern crate memmap2;
+extern crate rayon;
+
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+const MAGIC : &[u8; 8] = b"CRCTBL01";
+const MAX_PREFIX_LEN : usize = 32;
+const HEADER_LEN : usize = 8 + 4 + 4 + MAX_PREFIX_LEN + 8 + 8;
+const RECORD_LEN : usize = 8;
+const DISTINCT_OFFSET : usize = 24 + MAX_PREFIX_LEN;
+
+// Records sorted in memory at once while building, 1 GiB of them. Larger tables take one pass over
+// the candidates for each slice of the crc range
+const MAX_SORT_RECORDS : usize = 1 << 27;
+
+// Suffixes are indexed by a u32 so 74^5 is the largest space that fits
+pub const MAX_SUFFIX_LEN : usize = 5;
+
+// Number of distinct CRC32 values
+pub const CRC_SPACE : f64 = 4294967296.0;
+
+// Printable characters used in candidates, '0' to 'z' without the backslash
+pub fn charset() -> Vec<u8> {
+    (b'0'..=b'z').filter(|b| *b != b'\\').collect()
+}
+
+pub fn crc32(s : &[u8]) -> u32 {
+    let mut h = crc32fast::Hasher::new();
+    h.update(s);
+    h.finalize()
+}
+
+// Sorted (crc, candidate index) records for every string prefix + suffix
+// where the suffix ranges over all charset strings of suffix_len
+pub struct CrcTable {
+    mmap : Mmap,
+    prefix : Vec<u8>,
+    suffix_len : usize,
+    pub entries : u64,
+    pub distinct : u64,
+}
+
+impl CrcTable {
+    // Enumerates the whole candidate space and writes the sorted table to filename
+    pub fn build(filename : &str, prefix : &str, suffix_len : usize) -> std::io::Result<()> {
+        assert!(prefix.len() <= MAX_PREFIX_LEN, "Prefix longer than {}", MAX_PREFIX_LEN);
+        assert!((1..=MAX_SUFFIX_LEN).contains(&suffix_len),
+            "Suffix length must be 1 to {}", MAX_SUFFIX_LEN);
+
+        let chars = charset();
+        let entries = chars.len().pow(suffix_len as u32);
+        let passes = entries.div_ceil(MAX_SORT_RECORDS);
+
+        let mut prefix_h = crc32fast::Hasher::new();
+        prefix_h.update(prefix.as_bytes());
+
+        let mut file = BufWriter::new(File::create(filename)?);
+
+        let mut prefix_buf = [0; MAX_PREFIX_LEN];
+        prefix_buf[..prefix.len()].copy_from_slice(prefix.as_bytes());
+
+        // The distinct count is filled in once every record is written
+        file.write_all(MAGIC)?;
+        file.write_all(&(prefix.len() as u32).to_le_bytes())?;
+        file.write_all(&(suffix_len as u32).to_le_bytes())?;
+        file.write_all(&prefix_buf)?;
+        file.write_all(&(entries as u64).to_le_bytes())?;
+        file.write_all(&0u64.to_le_bytes())?;
+
+        let mut distinct = 0;
+
+        // Each pass keeps one slice of the crc range, written in order they make one sorted table
+        for pass in 0..passes as u64 {
+            let lo = (pass << 32) / passes as u64;
+            let hi = ((pass + 1) << 32) / passes as u64;
+
+            let mut records = records_in(&prefix_h, &chars, suffix_len, lo..hi);
+            records.par_sort_unstable();
+
+            if !records.is_empty() {
+                distinct += 1 + records.windows(2)
+                    .filter(|w| w[0].0 != w[1].0)
+                    .count() as u64;
+            }
+
+            for (crc, idx) in records {
+                file.write_all(&crc.to_le_bytes())?;
+                file.write_all(&idx.to_le_bytes())?;
+            }
+        }
+
+        let mut file = file.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(DISTINCT_OFFSET as u64))?;
+        file.write_all(&distinct.to_le_bytes())?;
+
+        file.flush()
+    }
+
+    pub fn open(filename : &str) -> std::io::Result<Self> {
+        let file = File::open(filename)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let bad = |msg : &str| std::io::Error::new(std::io::ErrorKind::InvalidData,
+            format!("{}: {}", filename, msg));
+
+        if mmap.len() < HEADER_LEN || &mmap[..8] != MAGIC {
+            return Err(bad("not a crc table"));
+        }
+
+        let prefix_len = read_u32(&mmap, 8) as usize;
+        let suffix_len = read_u32(&mmap, 12) as usize;
+
+        if prefix_len > MAX_PREFIX_LEN {
+            return Err(bad("bad prefix length"));
+        }
+
+        if !(1..=MAX_SUFFIX_LEN).contains(&suffix_len) {
+            return Err(bad("bad suffix length"));
+        }
+
+        let prefix = mmap[16..16 + prefix_len].to_vec();
+        let entries = read_u64(&mmap, 16 + MAX_PREFIX_LEN);
+        let distinct = read_u64(&mmap, DISTINCT_OFFSET);
+
+        if entries != charset().len().pow(suffix_len as u32) as u64 || distinct > entries {
+            return Err(bad("bad record counts"));
+        }
+
+        let records_len = entries.checked_mul(RECORD_LEN as u64)
+            .and_then(|n| n.checked_add(HEADER_LEN as u64));
+
+        if records_len != Some(mmap.len() as u64) {
+            return Err(bad("truncated table"));
+        }
+
+        Ok(CrcTable { mmap, prefix, suffix_len, entries, distinct })
+    }
+
+    // Fraction of the 2^32 crc values that have at least one string in the table
+    pub fn coverage(&self) -> f64 {
+        self.distinct as f64 / CRC_SPACE
+    }
+
+    fn record(&self, i : u64) -> (u32, u32) {
+        let off = HEADER_LEN + i as usize * RECORD_LEN;
+        (read_u32(&self.mmap, off), read_u32(&self.mmap, off + 4))
+    }
+
+    fn candidate(&self, idx : u32) -> String {
+        let chars = charset();
+        let mut idx = idx as usize;
+        let mut s = self.prefix.clone();
+        let start = s.len();
+
+        for _ in 0..self.suffix_len {
+            s.insert(start, chars[idx % chars.len()]);
+            idx /= chars.len();
+        }
+
+        String::from_utf8(s).unwrap()
+    }
+
+    // Binary search over the mapped records, returns a string with the given crc
+    pub fn lookup(&self, crc : u32) -> Option<String> {
+        let mut lo = 0;
+        let mut hi = self.entries;
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+
+            if self.record(mid).0 < crc {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        if lo < self.entries && self.record(lo).0 == crc {
+            Some(self.candidate(self.record(lo).1))
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for CrcTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "CrcTable ( Prefix = '{}' Suffix Length = {} Entries = {} Distinct = {} Coverage = {:.4}% )",
+            String::from_utf8_lossy(&self.prefix), self.suffix_len,
+            self.entries, self.distinct, self.coverage() * 100.0)
+    }
+}
+
+// Records of every candidate whose crc is in range, each first suffix char on its own thread
+fn records_in(prefix_h : &crc32fast::Hasher, chars : &[u8], suffix_len : usize,
+    range : std::ops::Range<u64>) -> Vec<(u32, u32)> {
+
+    let per_char = chars.len().pow(suffix_len as u32 - 1);
+
+    // Candidate order matches index order
+    (0..chars.len()).into_par_iter()
+        .flat_map_iter(|first| {
+            let mut h = prefix_h.clone();
+            h.update(&chars[first..=first]);
+
+            let mut buf = vec![0; suffix_len - 1];
+            let mut idx = first * per_char;
+            let mut out = Vec::new();
+            enumerate(&h, &mut buf, 0, chars, &mut |crc| {
+                if range.contains(&(crc as u64)) {
+                    out.push((crc, idx as u32));
+                }
+                idx += 1;
+            });
+
+            out
+        })
+        .collect()
+}
+
+fn enumerate<F>(h : &crc32fast::Hasher, buf : &mut [u8], pos : usize, chars : &[u8], f : &mut F)
+    where F : FnMut(u32) {
+
+    if pos == buf.len() {
+        f(h.clone().finalize());
+        return;
+    }
+
+    for c in chars {
+        buf[pos] = *c;
+
+        let mut h = h.clone();
+        h.update(&buf[pos..=pos]);
+
+        enumerate(&h, buf, pos + 1, chars, f);
+    }
+}
+
+fn read_u32(b : &[u8], off : usize) -> u32 {
+    let mut v = [0; 4];
+    v.copy_from_slice(&b[off..off + 4]);
+    u32::from_le_bytes(v)
+}
+
+fn read_u64(b : &[u8], off : usize) -> u64 {
+    let mut v = [0; 8];
+    v.copy_from_slice(&b[off..off + 8]);
+    u64::from_le_bytes(v)
+}
@@ -0,0 +1,116 @@
+use std::io::{BufRead, Write};
+
+use crate::simann::AnnealConfig;
+
+// Both sides send a heartbeat this often and drop the peer after DEAD_SECS of silence
+pub const HEARTBEAT_SECS : u64 = 5;
+pub const DEAD_SECS : u64 = 3 * HEARTBEAT_SECS;
+pub const DEFAULT_PORT : u16 = 8443;
+
+// A cipher text to anneal, sent to every worker given the job
+pub struct JobSpec {
+    pub id : usize,
+    pub cipher : String,
+    pub fitness : String,
+    pub max_fail : u64,
+    pub temp : i64,
+    pub text : String,
+}
+
+impl JobSpec {
+    pub fn config(&self) -> AnnealConfig {
+        AnnealConfig { max_fail: self.max_fail, temp: self.temp }
+    }
+}
+
+// The line protocol between coordinator and workers, a job line is followed by the bytes of its text
+//   worker:      HELLO name threads, RESULT job key, HEARTBEAT
+//   coordinator: JOB id cipher fitness max_fail temp length, HEARTBEAT
+pub enum Message {
+    Hello { name : String, threads : usize },
+    Job(JobSpec),
+    Result { job : usize, key : String },
+    Heartbeat,
+}
+
+pub fn write_message<W : Write>(w : &mut W, msg : &Message) -> std::io::Result<()> {
+    match msg {
+        Message::Hello { name, threads } => writeln!(w, "HELLO {} {}", name, threads)?,
+        Message::Job(job) => {
+            // Spaces in a fitness spec like "blend, chi:1" are not part of it and would split the field
+            let fitness : String = job.fitness.split_whitespace().collect();
+
+            writeln!(w, "JOB {} {} {} {} {} {}", job.id, job.cipher, fitness, job.max_fail, job.temp, job.text.len())?;
+            w.write_all(job.text.as_bytes())?;
+        },
+        Message::Result { job, key } => writeln!(w, "RESULT {} {}", job, key)?,
+        Message::Heartbeat => writeln!(w, "HEARTBEAT")?
+    }
+
+    w.flush()
+}
+
+fn field<T : std::str::FromStr>(fields : &[&str], i : usize) -> Result<T, String> {
+    fields.get(i)
+        .and_then(|f| f.parse::<T>().ok())
+        .ok_or_else(|| format!("Bad message {}", fields.join(" ")))
+}
+
+// Errors on a closed connection, a read timeout or a malformed message
+pub fn read_message<R : BufRead>(r : &mut R) -> Result<Message, String> {
+    let mut line = String::new();
+
+    if r.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+        return Err(String::from("Connection closed"));
+    }
+
+    let fields : Vec<&str> = line.split_whitespace().collect();
+
+    match fields.first() {
+        Some(&"HELLO") => Ok(Message::Hello { name: field(&fields, 1)?, threads: field(&fields, 2)? }),
+        Some(&"JOB") => {
+            let length : usize = field(&fields, 6)?;
+            let mut text = vec![0; length];
+            r.read_exact(&mut text).map_err(|e| e.to_string())?;
+
+            Ok(Message::Job(JobSpec {
+                id: field(&fields, 1)?,
+                cipher: field(&fields, 2)?,
+                fitness: field(&fields, 3)?,
+                max_fail: field(&fields, 4)?,
+                temp: field(&fields, 5)?,
+                text: String::from_utf8(text).map_err(|_| String::from("Job text is not UTF-8"))?,
+            }))
+        },
+        Some(&"RESULT") => Ok(Message::Result { job: field(&fields, 1)?, key: field(&fields, 2)? }),
+        Some(&"HEARTBEAT") => Ok(Message::Heartbeat),
+        _ => Err(format!("Bad message {}", line.trim_end()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_round_trip_with_spaced_fitness() {
+        let job = JobSpec { id: 3, cipher: String::from("playfair"), fitness: String::from("blend, chi:1"),
+            max_fail: 800, temp: 20, text: String::from("AB CD\nEF") };
+
+        let mut buf = Vec::new();
+        write_message(&mut buf, &Message::Job(job)).unwrap();
+        write_message(&mut buf, &Message::Heartbeat).unwrap();
+
+        let mut r = std::io::Cursor::new(buf);
+
+        match read_message(&mut r).unwrap() {
+            Message::Job(job) => {
+                assert_eq!((job.id, job.cipher.as_str(), job.fitness.as_str()), (3, "playfair", "blend,chi:1"));
+                assert_eq!((job.max_fail, job.temp, job.text.as_str()), (800, 20, "AB CD\nEF"));
+            },
+            _ => panic!("Expected a job")
+        }
+
+        assert!(matches!(read_message(&mut r), Ok(Message::Heartbeat)));
+    }
+}
@@ -0,0 +1,308 @@
+extern crate rand;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufRead;
+use std::ops::Range;
+
+use rand::Rng;
+
+use crate::freq::ENGLISH_FREQS;
+use crate::score::{self, NgramScore2, NgramScore4, WordListScore};
+use crate::segment::Segmenter;
+
+// Normalised index of coincidence of English, 26 * sum p^2, random text is 1.0
+const ENGLISH_KAPPA : f64 = 1.73;
+
+// Measures of how English a text looks, larger is better and all scale with the text length
+// so they can be added with weights
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Measure {
+    // The default blend of quadgrams and word coverage, score::score_text
+    Blend,
+    Quadgram,
+    Trigram,
+    Bigram,
+    // Chi-squared of the letter counts against English, negated
+    ChiSquared,
+    // Distance of the index of coincidence from English
+    IoC,
+    // Distance of the letter entropy from English
+    Entropy,
+    // Letters covered by word list words
+    Words,
+    // Log10 probability of the best split into word list words
+    Segment,
+}
+
+pub const MEASURES : [Measure; 9] = [Measure::Blend, Measure::Quadgram, Measure::Trigram,
+    Measure::Bigram, Measure::ChiSquared, Measure::IoC, Measure::Entropy, Measure::Words, Measure::Segment];
+
+impl Measure {
+    pub fn name(self) -> &'static str {
+        match self {
+            Measure::Blend => "blend",
+            Measure::Quadgram => "quadgram",
+            Measure::Trigram => "trigram",
+            Measure::Bigram => "bigram",
+            Measure::ChiSquared => "chi",
+            Measure::IoC => "ioc",
+            Measure::Entropy => "entropy",
+            Measure::Words => "words",
+            Measure::Segment => "segment",
+        }
+    }
+
+    pub fn parse(s : &str) -> Result<Self, String> {
+        MEASURES.iter()
+            .cloned()
+            .find(|m| m.name() == s.to_lowercase())
+            .ok_or_else(|| format!("Unknown measure {}, expected one of {}", s,
+                MEASURES.iter().map(|m| m.name()).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+// Log10 trigram probabilities, summed out of the quadgram counts
+struct TrigramScore {
+    log_probs : Vec<f64>,
+}
+
+impl TrigramScore {
+    fn create(filename : &str) -> Self {
+        let file = File::open(filename)
+            .unwrap_or_else(|_| panic!("Cannot open {}", filename));
+
+        let mut counts = vec![0.0; 26 * 26 * 26];
+
+        for l in BufReader::new(file).lines().map(|l| l.unwrap()) {
+            let mut it = l.split_ascii_whitespace();
+            let qgram = it.next().unwrap().to_uppercase();
+            let n = it.next().unwrap().parse::<f64>().unwrap();
+
+            counts[trigram_index(&qgram.as_bytes()[..3])] += n;
+        }
+
+        let total : f64 = counts.iter().sum();
+        let floor = (0.01 / total).log10();
+
+        let log_probs = counts.iter()
+            .map(|c| if *c > 0.0 { (c / total).log10() } else { floor })
+            .collect();
+
+        TrigramScore { log_probs }
+    }
+
+    fn score(&self, s : &str) -> f64 {
+        s.as_bytes().windows(3)
+            .map(|w| self.log_probs[trigram_index(w)])
+            .sum()
+    }
+}
+
+fn trigram_index(w : &[u8]) -> usize {
+    w.iter().fold(0, |i, b| i * 26 + (b - b'A') as usize)
+}
+
+fn letter_counts(text : &str) -> [usize; 26] {
+    let mut counts = [0; 26];
+
+    for b in text.bytes() {
+        counts[(b - b'A') as usize] += 1;
+    }
+
+    counts
+}
+
+// Chi-squared statistic of the letter counts of text against English
+pub fn chi_squared(text : &str) -> f64 {
+    letter_counts(text).iter().zip(ENGLISH_FREQS.iter())
+        .map(|(c, f)| {
+            let expected = f / 100.0 * text.len() as f64;
+            (*c as f64 - expected).powi(2) / expected
+        })
+        .sum()
+}
+
+// Normalised index of coincidence, 26 times the chance two letters picked from text match
+pub fn kappa(text : &str) -> f64 {
+    let n = text.len() as f64;
+
+    if n < 2.0 {
+        return 0.0;
+    }
+
+    let same : usize = letter_counts(text).iter().map(|c| c * c.saturating_sub(1)).sum();
+
+    26.0 * same as f64 / (n * (n - 1.0))
+}
+
+// Shannon entropy of the letters of text in bits
+pub fn entropy(text : &str) -> f64 {
+    let n = text.len() as f64;
+
+    letter_counts(text).iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / n;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn english_entropy() -> f64 {
+    ENGLISH_FREQS.iter()
+        .map(|f| {
+            let p = f / 100.0;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+// A weighted sum of measures, written as NAME:WEIGHT,NAME:WEIGHT with the weight defaulting to 1
+pub struct Fitness {
+    pub terms : Vec<(Measure, f64)>,
+    bigrams : Option<NgramScore2>,
+    trigrams : Option<TrigramScore>,
+    segmenter : Option<Segmenter>,
+    english_entropy : f64,
+}
+
+impl Fitness {
+    pub fn parse(spec : &str) -> Result<Vec<(Measure, f64)>, String> {
+        spec.split(',')
+            .filter(|t| !t.trim().is_empty())
+            .map(|t| {
+                let mut it = t.trim().splitn(2, ':');
+                let measure = Measure::parse(it.next().unwrap())?;
+                let weight = match it.next() {
+                    Some(w) => w.parse::<f64>().map_err(|_| format!("Bad weight {}", w))?,
+                    None => 1.0
+                };
+
+                Ok((measure, weight))
+            })
+            .collect()
+    }
+
+    // Loads only the n-gram tables and word list the terms need
+    pub fn create(spec : &str, bigram_file : &str, quadgram_file : &str) -> Result<Self, String> {
+        let terms = Fitness::parse(spec)?;
+
+        if terms.is_empty() {
+            return Err(String::from("Empty fitness"));
+        }
+
+        let bigrams = if terms.iter().any(|t| t.0 == Measure::Bigram) {
+            Some(NgramScore2::create(bigram_file))
+        } else {
+            None
+        };
+
+        let trigrams = if terms.iter().any(|t| t.0 == Measure::Trigram) {
+            Some(TrigramScore::create(quadgram_file))
+        } else {
+            None
+        };
+
+        let segmenter = if terms.iter().any(|t| t.0 == Measure::Segment) {
+            Some(Segmenter::create("wordlist.txt"))
+        } else {
+            None
+        };
+
+        Ok(Fitness { terms, bigrams, trigrams, segmenter, english_entropy: english_entropy() })
+    }
+
+
+    pub fn measure(&self, measure : Measure, ng_score : &NgramScore4, wl_score : &WordListScore,
+        text : &str, words : Option<&[Range<usize>]>) -> f64 {
+
+        let n = text.len() as f64;
+
+        match measure {
+            Measure::Blend => match words {
+                Some(words) => score::score_text_words(ng_score, wl_score, text, words),
+                None => score::score_text(ng_score, wl_score, text)
+            },
+            Measure::Quadgram => ng_score.score(text),
+            Measure::Trigram => self.trigrams.as_ref().unwrap().score(text),
+            Measure::Bigram => self.bigrams.as_ref().unwrap().score(text),
+            Measure::ChiSquared => -chi_squared(text),
+            Measure::IoC => -n * (kappa(text) - ENGLISH_KAPPA).abs(),
+            Measure::Entropy => -n * (entropy(text) - self.english_entropy).abs(),
+            Measure::Words => n * match words {
+                Some(words) => wl_score.word_coverage(text, words),
+                None => wl_score.coverage(text)
+            } as f64,
+            Measure::Segment => self.segmenter.as_ref().unwrap().segment(text).log_prob
+        }
+    }
+
+    // Words are the word boundaries of the cipher, if it kept them
+    pub fn score(&self, ng_score : &NgramScore4, wl_score : &WordListScore,
+        text : &str, words : Option<&[Range<usize>]>) -> f64 {
+
+        self.terms.iter()
+            .map(|(m, w)| w * self.measure(*m, ng_score, wl_score, text, words))
+            .sum()
+    }
+}
+
+impl std::fmt::Display for Fitness {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let terms : Vec<String> = self.terms.iter()
+            .map(|(m, w)| format!("{}:{}", m.name(), w))
+            .collect();
+
+        write!(f, "{}", terms.join(","))
+    }
+}
+
+// How well a fitness tells plaintext from decrypts that are a few key swaps away
+pub struct Separation {
+    // Fraction of near misses scored below the plaintext they came from
+    pub accuracy : f64,
+    // Mean score gap between plaintext and near miss over the standard deviation of the gaps
+    pub gap_sigma : f64,
+}
+
+// Near misses swap two letters throughout the text, the same as swapping two letters of a
+// substitution key, between 1 and max_swaps times
+pub fn near_miss<R : Rng + ?Sized>(text : &str, max_swaps : usize, rng : &mut R) -> String {
+    let mut map : Vec<u8> = (b'A'..=b'Z').collect();
+
+    for _ in 0..rng.gen_range(1, max_swaps + 1) {
+        let idxs = rand::seq::index::sample(rng, 26, 2);
+        map.swap(idxs.index(0), idxs.index(1));
+    }
+
+    text.bytes()
+        .map(|b| map[(b - b'A') as usize] as char)
+        .collect()
+}
+
+pub fn separation<F>(samples : &[String], misses : usize, max_swaps : usize,
+    rng : &mut dyn rand::RngCore, score : F) -> Separation where F : Fn(&str) -> f64 {
+
+    let mut gaps = Vec::new();
+
+    for s in samples {
+        let true_score = score(s);
+
+        for _ in 0..misses {
+            let miss = near_miss(s, max_swaps, rng);
+
+            // Swapping two letters that do not appear changes nothing
+            if miss != *s {
+                gaps.push(true_score - score(&miss));
+            }
+        }
+    }
+
+    let n = gaps.len().max(1) as f64;
+    let accuracy = gaps.iter().filter(|g| **g > 0.0).count() as f64 / n;
+    let mean = gaps.iter().sum::<f64>() / n;
+    let std = (gaps.iter().map(|g| (g - mean).powi(2)).sum::<f64>() / n).sqrt();
+
+    Separation { accuracy, gap_sigma: if std > 0.0 { mean / std } else { 0.0 } }
+}
@@ -0,0 +1,190 @@
+extern crate rand;
+
+use rand::{Rng, RngCore};
+
+use crate::fitness::Fitness;
+use crate::playfair::{self, PLAYFAIR_ALPHABET};
+use crate::score::{NgramScore4, WordListScore};
+use crate::simann::KeySpace;
+use crate::text::CipherText;
+
+// Letters in each of the two keyed squares
+const SQUARE : usize = 25;
+
+// Digraphic ciphers with two keyed 5x5 squares. The letters of each plaintext digraph are found in
+// one square each and the cipher digraph is read from the other two corners of the rectangle they
+// form. Four-square finds them in two plain alphabet squares and reads from the keyed squares, the
+// Two-square ciphers find and read in the keyed squares, side by side or one above the other
+#[derive(Clone, Copy, PartialEq)]
+pub enum SquareCipher {
+    FourSquare,
+    TwoSquare,
+    TwoSquareVertical,
+}
+
+impl SquareCipher {
+    pub fn name(self) -> &'static str {
+        match self {
+            SquareCipher::FourSquare => "foursquare",
+            SquareCipher::TwoSquare => "twosquare",
+            SquareCipher::TwoSquareVertical => "twosquare-vertical"
+        }
+    }
+
+    pub fn from_name(name : &str) -> Option<Self> {
+        [SquareCipher::FourSquare, SquareCipher::TwoSquare, SquareCipher::TwoSquareVertical].iter()
+            .copied()
+            .find(|kind| kind.name() == name)
+    }
+
+    // Squares the plaintext letters are found in and the squares the cipher letters are read from
+    fn squares(self, key : &[u8]) -> ([&[u8]; 2], [&[u8]; 2]) {
+        let (k1, k2) = key.split_at(SQUARE);
+        let plain = PLAYFAIR_ALPHABET.as_bytes();
+
+        match self {
+            SquareCipher::FourSquare => ([plain, plain], [k1, k2]),
+            // Left and right squares, the corners swap sides
+            SquareCipher::TwoSquare => ([k1, k2], [k2, k1]),
+            // Top and bottom squares, the corners stay in the same square
+            SquareCipher::TwoSquareVertical => ([k1, k2], [k1, k2])
+        }
+    }
+
+    // The key as the two squares side by side
+    pub fn format_key(self, key : &str) -> String {
+        let key = key.as_bytes();
+
+        (0..5)
+            .map(|row| format!("{}   {}",
+                String::from_utf8_lossy(&key[row * 5..row * 5 + 5]),
+                String::from_utf8_lossy(&key[SQUARE + row * 5..SQUARE + row * 5 + 5])))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// Row and column of every letter in a square
+fn positions(square : &[u8]) -> [(usize, usize); 26] {
+    let mut pos = [(0, 0); 26];
+
+    for (i, b) in square.iter().enumerate() {
+        pos[(b - b'A') as usize] = (i / 5, i % 5);
+    }
+
+    pos
+}
+
+// Finds the letters of each digraph in the find squares and reads the other corners of their
+// rectangle from the out squares, a trailing odd letter is dropped
+fn apply(text : &str, find : [&[u8]; 2], out : [&[u8]; 2]) -> String {
+    let pos = [positions(find[0]), positions(find[1])];
+    let mut result = String::with_capacity(text.len());
+
+    for d in text.as_bytes().chunks_exact(2) {
+        let (r1, c1) = pos[0][(d[0] - b'A') as usize];
+        let (r2, c2) = pos[1][(d[1] - b'A') as usize];
+
+        result.push(out[0][r1 * 5 + c2] as char);
+        result.push(out[1][r2 * 5 + c1] as char);
+    }
+
+    result
+}
+
+// Encrypts a text already passed through playfair::prepare with the two squares one after the other
+pub fn encrypt(kind : SquareCipher, prepared : &str, key : &str) -> String {
+    let (find, out) = kind.squares(key.as_bytes());
+    apply(prepared, find, out)
+}
+
+pub fn decrypt(kind : SquareCipher, cipher : &str, key : &str) -> String {
+    let (find, out) = kind.squares(key.as_bytes());
+    apply(cipher, out, find)
+}
+
+// Two squares from keywords, either square can be given as its 25 letters
+pub fn keyword_key(keyword1 : &str, keyword2 : &str) -> String {
+    playfair::keyword_square(keyword1) + &playfair::keyword_square(keyword2)
+}
+
+pub fn random_key(rng : &mut dyn RngCore) -> String {
+    playfair::random_key(rng) + &playfair::random_key(rng)
+}
+
+pub struct FourSquare {
+    pub kind : SquareCipher,
+    pub cipher : CipherText,
+    fitness : Fitness,
+}
+
+impl FourSquare {
+    // The squares have no J, it is read as I like the plaintext
+    pub fn new(kind : SquareCipher, mut cipher : CipherText, fitness : Fitness) -> Self {
+        cipher.letters = playfair::merge_j(&cipher.letters);
+        FourSquare { kind, cipher, fitness }
+    }
+}
+
+impl KeySpace for FourSquare {
+    fn generate_key(&self, rng : &mut dyn RngCore) -> String {
+        random_key(rng)
+    }
+
+    // The Playfair moves on one of the two squares
+    fn random_swap_key(&self, key : &str, rng : &mut dyn RngCore) -> String {
+        let (k1, k2) = key.split_at(SQUARE);
+
+        if rng.gen::<bool>() {
+            playfair::grid_swap_key(k1, 5, 2, rng) + k2
+        } else {
+            String::from(k1) + &playfair::grid_swap_key(k2, 5, 2, rng)
+        }
+    }
+
+    fn decrypt(&self, key : &str) -> String {
+        decrypt(self.kind, &self.cipher.letters, key)
+    }
+
+    fn score(&self, ng_score : &NgramScore4, wl_score : &WordListScore, text : &str) -> f64 {
+        self.fitness.score(ng_score, wl_score, text, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // EXAMP     KEYWO
+    // LBCDF     RDABC
+    // GHIKN     FGHIL
+    // OQRST     MNPQS
+    // UVWYZ     TUVXZ
+    fn key() -> String {
+        keyword_key("EXAMPLE", "KEYWORD")
+    }
+
+    #[test]
+    fn squares_corner_mappings() {
+        // H and E in the plain squares at (1, 2) and (0, 4), read at (1, 4) of the first keyed
+        // square and (0, 2) of the second
+        assert_eq!(encrypt(SquareCipher::FourSquare, "HE", &key()), "FY");
+
+        // H at (2, 1) of the first square and S at (3, 4) of the second, the corners swap squares
+        assert_eq!(encrypt(SquareCipher::TwoSquare, "HS", &key()), "LQ");
+
+        // The same corners stay in their own square
+        assert_eq!(encrypt(SquareCipher::TwoSquareVertical, "HS", &key()), "NN");
+    }
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        let plain = playfair::prepare("Help me Obi-Wan Kenobi, you're my only hope");
+
+        for kind in [SquareCipher::FourSquare, SquareCipher::TwoSquare, SquareCipher::TwoSquareVertical] {
+            let cipher = encrypt(kind, &plain, &key());
+            assert_ne!(cipher, plain);
+            assert_eq!(decrypt(kind, &cipher, &key()), plain);
+        }
+    }
+}
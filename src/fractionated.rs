@@ -0,0 +1,295 @@
+extern crate rand;
+
+use rand::RngCore;
+use rand::seq::SliceRandom;
+
+use crate::fitness::Fitness;
+use crate::keyword;
+use crate::playfair::{self, PLAYFAIR_ALPHABET};
+use crate::score::{NgramScore4, WordListScore};
+use crate::simann::KeySpace;
+
+// 3x3x3 cube alphabet, the letters and one extra symbol that plaintext never uses
+pub const TRIFID_ALPHABET : &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ+";
+
+// Scores only know letters, the extra Trifid symbol decrypts as this one
+const EXTRA_PLAIN : char = 'X';
+
+// Periods tried when detecting the period, 0 stands for the whole text as one block
+pub const MAX_PERIOD : usize = 20;
+
+// Bifid writes each letter as its row and column in a 5x5 square, Trifid as its layer, row and
+// column in a 3x3x3 cube. Within each period the coordinates of the letters are written out one
+// axis after another and read back in groups as the cipher letters
+#[derive(Clone, Copy, PartialEq)]
+pub enum Fractionation {
+    Bifid,
+    Trifid,
+}
+
+impl Fractionation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Fractionation::Bifid => "bifid",
+            Fractionation::Trifid => "trifid"
+        }
+    }
+
+    pub fn alphabet(self) -> &'static str {
+        match self {
+            Fractionation::Bifid => PLAYFAIR_ALPHABET,
+            Fractionation::Trifid => TRIFID_ALPHABET
+        }
+    }
+
+    // Letters along each axis of the grid
+    pub fn side(self) -> usize {
+        match self {
+            Fractionation::Bifid => 5,
+            Fractionation::Trifid => 3
+        }
+    }
+
+    // Coordinates of each letter
+    pub fn dims(self) -> usize {
+        match self {
+            Fractionation::Bifid => 2,
+            Fractionation::Trifid => 3
+        }
+    }
+
+    // Plaintext ready to encrypt, Bifid merges J into I
+    pub fn prepare(self, plain : &str) -> String {
+        match self {
+            Fractionation::Bifid => playfair::merge_j(plain),
+            Fractionation::Trifid => plain.chars()
+                .filter(|c| c.is_ascii_alphabetic())
+                .map(|c| c.to_ascii_uppercase())
+                .collect()
+        }
+    }
+
+    // The symbols of a cipher text that are in the grid
+    pub fn cipher_letters(self, text : &str) -> String {
+        let alphabet = self.alphabet();
+
+        text.chars()
+            .map(|c| c.to_ascii_uppercase())
+            .filter(|c| alphabet.contains(*c))
+            .collect()
+    }
+
+    pub fn random_key(self, rng : &mut dyn RngCore) -> String {
+        let mut k = Vec::from(self.alphabet());
+        k.shuffle(rng);
+
+        String::from_utf8(k).unwrap()
+    }
+
+    // The grid a keyword generates, its distinct letters followed by the rest of the alphabet
+    pub fn keyword_key(self, keyword : &str) -> String {
+        keyword::keyword_alphabet(keyword, self.alphabet())
+    }
+
+    // The key as rows of the square, or of each layer of the cube side by side
+    pub fn format_key(self, key : &str) -> String {
+        let side = self.side();
+        let layer = side * side;
+        let key = key.as_bytes();
+
+        (0..side)
+            .map(|row| {
+                (0..key.len() / layer)
+                    .map(|l| String::from_utf8_lossy(&key[l * layer + row * side..l * layer + (row + 1) * side]).into_owned())
+                    .collect::<Vec<_>>()
+                    .join("   ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// Position in the key of every symbol
+fn positions(key : &str) -> [usize; 128] {
+    let mut pos = [0; 128];
+
+    for (i, b) in key.bytes().enumerate() {
+        pos[b as usize] = i;
+    }
+
+    pos
+}
+
+// Blocks of period letters, the last one shorter, period 0 is one block
+fn blocks(len : usize, period : usize) -> impl Iterator<Item = std::ops::Range<usize>> {
+    let period = if period == 0 { len.max(1) } else { period };
+    (0..len).step_by(period).map(move |start| start..(start + period).min(len))
+}
+
+// Coordinate axis of a grid position, most significant first
+fn coord(kind : Fractionation, pos : usize, axis : usize) -> usize {
+    (pos / kind.side().pow((kind.dims() - 1 - axis) as u32)) % kind.side()
+}
+
+pub fn encrypt(kind : Fractionation, prepared : &str, key : &str, period : usize) -> String {
+    let pos = positions(key);
+    let text = prepared.as_bytes();
+    let key = key.as_bytes();
+    let mut out = String::with_capacity(text.len());
+
+    for block in blocks(text.len(), period) {
+        let digits : Vec<usize> = (0..kind.dims())
+            .flat_map(|axis| text[block.clone()].iter().map(move |b| coord(kind, pos[*b as usize], axis)))
+            .collect();
+
+        for group in digits.chunks(kind.dims()) {
+            let p = group.iter().fold(0, |p, d| p * kind.side() + d);
+            out.push(key[p] as char);
+        }
+    }
+
+    out
+}
+
+pub fn decrypt(kind : Fractionation, cipher : &str, key : &str, period : usize) -> String {
+    let pos = positions(key);
+    let text = cipher.as_bytes();
+    let key = key.as_bytes();
+    let mut out = String::with_capacity(text.len());
+
+    for block in blocks(text.len(), period) {
+        let n = block.len();
+        let digits : Vec<usize> = text[block].iter()
+            .flat_map(|b| (0..kind.dims()).map(move |axis| coord(kind, pos[*b as usize], axis)))
+            .collect();
+
+        for i in 0..n {
+            let p = (0..kind.dims()).fold(0, |p, axis| p * kind.side() + digits[axis * n + i]);
+            out.push(key[p] as char);
+        }
+    }
+
+    out
+}
+
+// How far from random the groups of cipher letters a period links are, for every period up to
+// max_period and the whole text (period 0), best first. In a block of n letters the coordinates
+// of plaintext letter j end up in the letters at j / dims, (n + j) / dims and so on, so at the
+// right period these groups are as uneven as English digraphs (Bifid) or trigraphs (Trifid).
+// The measure is the index of coincidence of the groups times the number of possible groups,
+// 1.0 when random
+pub fn period_scores(kind : Fractionation, cipher : &str, max_period : usize) -> Vec<(usize, f64)> {
+    let text = cipher.as_bytes();
+    let pos = positions(kind.alphabet());
+    let dims = kind.dims();
+    let groups = kind.alphabet().len().pow(dims as u32);
+
+    // Shorter periods than dims put the same letter in a group more than once
+    let mut scores : Vec<(usize, f64)> = (dims..=max_period.min(text.len() / 2)).chain(Some(0))
+        .map(|period| {
+            let mut counts = vec![0u64; groups];
+
+            for block in blocks(text.len(), period).filter(|b| period == 0 || b.len() == period) {
+                let n = block.len();
+
+                for j in (0..n).step_by(dims) {
+                    let g = (0..dims).fold(0, |g, axis| {
+                        g * kind.alphabet().len() + pos[text[block.start + (axis * n + j) / dims] as usize]
+                    });
+                    counts[g] += 1;
+                }
+            }
+
+            let total : u64 = counts.iter().sum();
+            let same : u64 = counts.iter().map(|c| c * c.saturating_sub(1)).sum();
+            let ioc = same as f64 / (total * total.saturating_sub(1)).max(1) as f64;
+
+            (period, ioc * groups as f64)
+        })
+        .collect();
+
+    scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    scores
+}
+
+pub fn detect_period(kind : Fractionation, cipher : &str) -> usize {
+    period_scores(kind, cipher, MAX_PERIOD)[0].0
+}
+
+pub struct Fractionated {
+    pub kind : Fractionation,
+    pub cipher : String,
+    pub period : usize,
+    fitness : Fitness,
+}
+
+impl Fractionated {
+    // The period is detected from the cipher text unless given
+    pub fn new(kind : Fractionation, text : &str, period : Option<usize>, fitness : Fitness) -> Self {
+        let cipher = kind.cipher_letters(text);
+        let period = period.unwrap_or_else(|| detect_period(kind, &cipher));
+
+        Fractionated { kind, cipher, period, fitness }
+    }
+}
+
+impl KeySpace for Fractionated {
+    fn generate_key(&self, rng : &mut dyn RngCore) -> String {
+        self.kind.random_key(rng)
+    }
+
+    fn random_swap_key(&self, key : &str, rng : &mut dyn RngCore) -> String {
+        playfair::grid_swap_key(key, self.kind.side(), self.kind.dims(), rng)
+    }
+
+    fn decrypt(&self, key : &str) -> String {
+        decrypt(self.kind, &self.cipher, key, self.period).replace('+', &EXTRA_PLAIN.to_string())
+    }
+
+    fn score(&self, ng_score : &NgramScore4, wl_score : &WordListScore, text : &str) -> f64 {
+        self.fitness.score(ng_score, wl_score, text, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN : &str = "It was the best of times, it was the worst of times, it was the age of wisdom, it was the age \
+        of foolishness, it was the epoch of belief, it was the epoch of incredulity, it was the season of Light, \
+        it was the season of Darkness, it was the spring of hope, it was the winter of despair, we had everything \
+        before us, we had nothing before us, we were all going direct to Heaven, we were all going direct the \
+        other way";
+
+    #[test]
+    fn bifid_known_cipher() {
+        let key = "BGWKZQPNDSIOAXEFCLUMTHYVR";
+        assert_eq!(encrypt(Fractionation::Bifid, "FLEEATONCE", key, 0), "UAEOLWRINS");
+        assert_eq!(decrypt(Fractionation::Bifid, "UAEOLWRINS", key, 0), "FLEEATONCE");
+    }
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        for kind in [Fractionation::Bifid, Fractionation::Trifid] {
+            let key = kind.keyword_key("FRACTIONATED");
+            let plain = kind.prepare(PLAIN);
+
+            // Periods that do and do not divide the length, and the whole text
+            for period in [5, 7, 0] {
+                let cipher = encrypt(kind, &plain, &key, period);
+                assert_ne!(cipher, plain);
+                assert_eq!(decrypt(kind, &cipher, &key, period), plain);
+            }
+        }
+    }
+
+    #[test]
+    fn detect_period_of_english() {
+        for (kind, period) in [(Fractionation::Bifid, 7), (Fractionation::Trifid, 5)] {
+            let key = kind.keyword_key("FRACTIONATED");
+            let cipher = encrypt(kind, &kind.prepare(PLAIN), &key, period);
+
+            assert_eq!(detect_period(kind, &cipher), period);
+        }
+    }
+}
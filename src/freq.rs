@@ -1,9 +1,14 @@
 use std::cmp::Ordering;
 
-const ALPHABET : &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
-
 const ENGLFREQS : &str = "ETAOINSRHDLUCMFYWGPBVKXQJZ";
 
+// Percent of English text made of each letter A to Z
+pub const ENGLISH_FREQS : [f64; 26] = [
+    8.167, 1.492, 2.782, 4.253, 12.702, 2.228, 2.015, 6.094, 6.966,
+    0.153, 0.772, 4.025, 2.406, 6.749, 7.507, 1.929, 0.095, 5.987,
+    6.327, 9.056, 2.758, 0.978, 2.360, 0.150, 1.974, 0.074
+];
+
 pub struct Freq {
     pub freqs :  Vec<(char, u32)>,
     pub score : u32
@@ -28,7 +33,7 @@ impl Freq {
 
 impl PartialEq for Freq {
     fn eq(&self, other: &Self) -> bool {
-        (self.score == other.score)
+        self.score == other.score
     }
 }
 
@@ -36,13 +41,7 @@ impl Eq for Freq {}
 
 impl PartialOrd for Freq {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.score > other.score {
-            Some(Ordering::Greater)
-        } else if self.score < other.score {
-            Some(Ordering::Less)
-        } else {
-            Some(Ordering::Equal)
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -66,7 +65,7 @@ impl From<&str> for Freq {
             freqs.push((c, text.chars().filter(|v| c == *v).count() as u32));
         }
 
-        &mut freqs.sort_by(|p1, p2| p2.1.cmp(&p1.1));
+        freqs.sort_by_key(|p| std::cmp::Reverse(p.1));
 
         let score = Freq::calc_score(&freqs);
 
@@ -84,7 +83,7 @@ impl std::fmt::Display for Freq {
 
             count += 1;
             if count == 4 {
-                write!(f, "\n")?;
+                writeln!(f)?;
                 count = 0
             }
         }
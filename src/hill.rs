@@ -0,0 +1,421 @@
+extern crate rand;
+extern crate rayon;
+
+use std::fmt;
+
+use rand::{Rng, RngCore};
+use rayon::prelude::*;
+
+use crate::fitness;
+use crate::score::NgramScore4;
+
+const MODULUS : i64 = 26;
+
+// Letter that pads the plaintext to a whole number of blocks
+const PAD : u8 = b'X';
+
+// Matrix sizes the solvers try
+pub const SIZES : [usize; 3] = [2, 3, 4];
+
+// Rows with the lowest chi-squared kept for the ciphertext only attack, every ordering of n of them is tried
+const ROW_CANDIDATES : usize = 12;
+
+// Known plaintext blocks searched for n of them that make an invertible matrix
+const MAX_KNOWN_BLOCKS : usize = 16;
+
+// n x n matrix over Z26, row major
+#[derive(Clone, PartialEq)]
+pub struct Matrix {
+    pub n : usize,
+    pub cells : Vec<i64>,
+}
+
+fn modulo(x : i64) -> i64 {
+    x.rem_euclid(MODULUS)
+}
+
+fn gcd(a : i64, b : i64) -> i64 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
+// Multiplicative inverse mod 26, if x has one
+fn inverse_mod(x : i64) -> Option<i64> {
+    (1..MODULUS).find(|i| modulo(x * i) == 1)
+}
+
+impl Matrix {
+    pub fn new(n : usize, cells : Vec<i64>) -> Self {
+        assert_eq!(cells.len(), n * n, "Matrix needs {} cells", n * n);
+        Matrix { n, cells: cells.into_iter().map(modulo).collect() }
+    }
+
+    // Numbers separated by commas or spaces, or letters with A = 0, read row by row
+    pub fn parse(s : &str) -> Result<Self, String> {
+        let cells : Vec<i64> = if s.chars().any(|c| c.is_ascii_digit()) {
+            s.split(|c : char| c == ',' || c.is_whitespace())
+                .filter(|t| !t.is_empty())
+                .map(|t| t.parse::<i64>().map_err(|_| format!("Bad matrix entry {}", t)))
+                .collect::<Result<_, _>>()?
+        } else {
+            s.bytes()
+                .filter(|b| b.is_ascii_alphabetic())
+                .map(|b| (b.to_ascii_uppercase() - b'A') as i64)
+                .collect()
+        };
+
+        match SIZES.iter().find(|&&n| n * n == cells.len()) {
+            Some(&n) => Ok(Matrix::new(n, cells)),
+            None => Err(format!("Key has {} entries, expected 4, 9 or 16", cells.len()))
+        }
+    }
+
+    pub fn random_invertible(n : usize, rng : &mut dyn RngCore) -> Self {
+        loop {
+            let m = Matrix::new(n, (0..n * n).map(|_| rng.gen_range(0, MODULUS)).collect());
+
+            if m.is_invertible() {
+                return m;
+            }
+        }
+    }
+
+    pub fn get(&self, row : usize, col : usize) -> i64 {
+        self.cells[row * self.n + col]
+    }
+
+    // The matrix without one row and column
+    fn minor(&self, row : usize, col : usize) -> Matrix {
+        let cells = (0..self.n).filter(|&r| r != row)
+            .flat_map(|r| (0..self.n).filter(move |&c| c != col).map(move |c| self.get(r, c)))
+            .collect();
+
+        Matrix { n: self.n - 1, cells }
+    }
+
+    // Determinant mod 26 by cofactor expansion along the first row
+    pub fn det(&self) -> i64 {
+        if self.n == 1 {
+            return self.cells[0];
+        }
+
+        modulo((0..self.n)
+            .map(|c| {
+                let sign = if c % 2 == 0 { 1 } else { -1 };
+                sign * self.get(0, c) * self.minor(0, c).det()
+            })
+            .sum())
+    }
+
+    // Invertible mod 26 when the determinant shares no factor with 26
+    pub fn is_invertible(&self) -> bool {
+        gcd(self.det(), MODULUS) == 1
+    }
+
+    // The inverse mod 26, the adjugate divided by the determinant
+    pub fn inverse(&self) -> Option<Matrix> {
+        let det_inv = inverse_mod(self.det())?;
+
+        if self.n == 1 {
+            return Some(Matrix::new(1, vec![det_inv]));
+        }
+
+        let cells = (0..self.n * self.n)
+            .map(|i| {
+                let (r, c) = (i / self.n, i % self.n);
+                let sign = if (r + c) % 2 == 0 { 1 } else { -1 };
+                det_inv * sign * self.minor(c, r).det()
+            })
+            .collect();
+
+        Some(Matrix::new(self.n, cells))
+    }
+
+    pub fn mul(&self, other : &Matrix) -> Matrix {
+        let n = self.n;
+        let cells = (0..n * n)
+            .map(|i| (0..n).map(|k| self.get(i / n, k) * other.get(k, i % n)).sum())
+            .collect();
+
+        Matrix::new(n, cells)
+    }
+
+    // The letters of the cells, A = 0
+    pub fn letters(&self) -> String {
+        self.cells.iter().map(|c| (b'A' + *c as u8) as char).collect()
+    }
+
+    // Each block of n letters as a column vector multiplied by the matrix, a trailing partial block is dropped
+    pub fn apply(&self, text : &str) -> String {
+        let mut out = String::with_capacity(text.len());
+
+        for block in text.as_bytes().chunks_exact(self.n) {
+            for r in 0..self.n {
+                let v : i64 = (0..self.n).map(|c| self.get(r, c) * (block[c] - b'A') as i64).sum();
+                out.push((b'A' + modulo(v) as u8) as char);
+            }
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Matrix {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        for r in 0..self.n {
+            if r > 0 {
+                writeln!(f)?;
+            }
+
+            for c in 0..self.n {
+                write!(f, "{:>3}", self.get(r, c))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Upper cased letters, padded to a whole number of blocks, ready to be encrypted
+pub fn prepare(plain : &str, n : usize) -> String {
+    let mut letters : Vec<u8> = plain.bytes()
+        .filter(|b| b.is_ascii_alphabetic())
+        .map(|b| b.to_ascii_uppercase())
+        .collect();
+
+    while !letters.len().is_multiple_of(n) {
+        letters.push(PAD);
+    }
+
+    String::from_utf8(letters).unwrap()
+}
+
+pub fn encrypt(prepared : &str, key : &Matrix) -> Result<String, String> {
+    if !key.is_invertible() {
+        return Err(format!("Key {} is not invertible mod 26", key.letters()));
+    }
+
+    Ok(key.apply(prepared))
+}
+
+pub fn decrypt(cipher : &str, key : &Matrix) -> Result<String, String> {
+    key.inverse()
+        .map(|inverse| inverse.apply(cipher))
+        .ok_or_else(|| format!("Key {} is not invertible mod 26", key.letters()))
+}
+
+// A key found by an attack, with the decrypt and its quadgram score
+pub struct HillSolution {
+    pub key : Matrix,
+    pub inverse : Matrix,
+    pub plain : String,
+    pub score : f64,
+}
+
+impl HillSolution {
+    fn from_inverse(inverse : Matrix, cipher : &str, ng_score : &NgramScore4) -> Option<Self> {
+        let key = inverse.inverse()?;
+        let plain = inverse.apply(cipher);
+        let score = ng_score.score(&plain);
+
+        Some(HillSolution { key, inverse, plain, score })
+    }
+}
+
+// Every way to pick k of 0..m in increasing order
+fn combinations(m : usize, k : usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+
+    (k - 1..m)
+        .flat_map(|last| combinations(last, k - 1).into_iter().map(move |mut c| {
+            c.push(last);
+            c
+        }))
+        .collect()
+}
+
+// Every way to pick k of 0..m in any order
+fn permutations(m : usize, k : usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+
+    permutations(m, k - 1).into_iter()
+        .flat_map(|p| (0..m).filter(|i| !p.contains(i)).collect::<Vec<_>>().into_iter().map(move |i| {
+            let mut p = p.clone();
+            p.push(i);
+            p
+        }))
+        .collect()
+}
+
+// The key from plaintext and the cipher text it encrypts to, both starting at a block boundary.
+// With n plaintext blocks as the columns of an invertible matrix P and their cipher blocks as the
+// columns of C, C = K P so K = C P^-1. The key must encrypt every other known block the same way
+pub fn known_plaintext(plain : &str, cipher : &str, n : usize) -> Result<(Matrix, Matrix), String> {
+    let blocks = (plain.len().min(cipher.len()) / n).min(MAX_KNOWN_BLOCKS);
+
+    if blocks < n {
+        return Err(format!("Known plaintext needs at least {} letters for a {}x{} key", n * n, n, n));
+    }
+
+    // Block columns of a text as a matrix
+    let columns = |text : &[u8], picked : &[usize]| {
+        let cells = (0..n * n)
+            .map(|i| (text[picked[i % n] * n + i / n] - b'A') as i64)
+            .collect();
+        Matrix::new(n, cells)
+    };
+
+    let known = n * blocks;
+
+    for picked in combinations(blocks, n) {
+        let p = columns(plain.as_bytes(), &picked);
+
+        if let Some(p_inv) = p.inverse() {
+            let key = columns(cipher.as_bytes(), &picked).mul(&p_inv);
+
+            if key.apply(&plain[..known]) == cipher[..known] {
+                if let Some(inverse) = key.inverse() {
+                    return Ok((key, inverse));
+                }
+            }
+        }
+    }
+
+    Err(format!("No {} of the known plaintext blocks give an invertible {}x{} key", n, n, n))
+}
+
+// Known plaintext at offset letters into the cipher text, or at every offset when not given,
+// keeping the key with the best decrypt. Without one the error of the last offset tried says why
+pub fn crib_attack(cipher : &str, crib : &str, n : usize, offset : Option<usize>,
+    ng_score : &NgramScore4) -> Result<HillSolution, String> {
+
+    let offsets = match offset {
+        Some(offset) => offset..offset + 1,
+        None => 0..cipher.len().saturating_sub(crib.len()) + 1
+    };
+
+    let mut error = format!("Known plaintext of {} letters does not fit in the cipher text", crib.len());
+
+    offsets
+        .filter(|&o| o + crib.len() <= cipher.len())
+        .filter_map(|o| {
+            // Skip to the first block boundary inside the crib
+            let skip = (n - o % n) % n;
+            let plain = crib.get(skip..)?;
+
+            match known_plaintext(plain, &cipher[o + skip..], n) {
+                Ok((_, inverse)) => HillSolution::from_inverse(inverse, cipher, ng_score),
+                Err(e) => {
+                    error = e;
+                    None
+                }
+            }
+        })
+        .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap())
+        .ok_or(error)
+}
+
+// Each row of the inverse key makes one letter of every plaintext block, so every possible row
+// is tried on its own and the ones whose letters are closest to English by chi-squared are kept
+pub fn best_rows(cipher : &str, n : usize, keep : usize) -> Vec<(Vec<i64>, f64)> {
+    let blocks : Vec<&[u8]> = cipher.as_bytes().chunks_exact(n).collect();
+
+    let mut rows : Vec<(Vec<i64>, f64)> = (0..(MODULUS as usize).pow(n as u32)).into_par_iter()
+        .map(|i| {
+            let row : Vec<i64> = (0..n).map(|c| (i / (MODULUS as usize).pow(c as u32)) as i64 % MODULUS).collect();
+            let letters : String = blocks.iter()
+                .map(|b| {
+                    let v : i64 = (0..n).map(|c| row[c] * (b[c] - b'A') as i64).sum();
+                    (b'A' + modulo(v) as u8) as char
+                })
+                .collect();
+
+            (row, fitness::chi_squared(&letters))
+        })
+        .collect();
+
+    rows.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    rows.truncate(keep);
+    rows
+}
+
+// Solves for the inverse key row by row, then puts the best rows in the order whose interleaved
+// letters score best as quadgrams
+pub fn ciphertext_only(cipher : &str, n : usize, ng_score : &NgramScore4) -> Option<HillSolution> {
+    let rows = best_rows(cipher, n, ROW_CANDIDATES);
+
+    permutations(rows.len(), n).into_par_iter()
+        .filter_map(|order| {
+            let inverse = Matrix::new(n, order.iter().flat_map(|&r| rows[r].0.clone()).collect());
+            HillSolution::from_inverse(inverse, cipher, ng_score)
+        })
+        .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN : &str = "It was the best of times it was the worst of times it was the age of wisdom it was the age \
+        of foolishness it was the epoch of belief it was the epoch of incredulity";
+
+    fn key() -> Matrix {
+        Matrix::parse("GYBNQKURP").unwrap()
+    }
+
+    #[test]
+    fn det_and_inverse() {
+        let key = key();
+        assert_eq!(key.det(), 25);
+        assert_eq!(key.inverse().unwrap().cells, vec![8, 5, 10, 21, 8, 21, 21, 12, 8]);
+        assert_eq!(key.mul(&key.inverse().unwrap()).cells, vec![1, 0, 0, 0, 1, 0, 0, 0, 1]);
+
+        // Even determinant
+        let singular = Matrix::new(2, vec![2, 4, 6, 8]);
+        assert_eq!(singular.det(), 18);
+        assert!(singular.inverse().is_none());
+    }
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        assert_eq!(encrypt("ACT", &key()).unwrap(), "POH");
+
+        let plain = prepare(PLAIN, 3);
+        let cipher = encrypt(&plain, &key()).unwrap();
+        assert_eq!(decrypt(&cipher, &key()).unwrap(), plain);
+    }
+
+    #[test]
+    fn known_plaintext_finds_the_key() {
+        let plain = prepare(PLAIN, 3);
+        let cipher = encrypt(&plain, &key()).unwrap();
+
+        let (found, inverse) = known_plaintext(&plain[..30], &cipher[..30], 3).unwrap();
+        assert!(found == key());
+        assert!(inverse == key().inverse().unwrap());
+
+        assert!(known_plaintext("ITWAS", &cipher[..5], 3).is_err());
+    }
+
+    #[test]
+    fn crib_attack_finds_the_key() {
+        let ng_score = NgramScore4::create("english_quadgrams.txt");
+        let plain = prepare(PLAIN, 3);
+        let cipher = encrypt(&plain, &key()).unwrap();
+
+        // The crib starts off a block boundary, at letter 40
+        let crib = "TWASTHEAGEOFWISDOMITWAST";
+        assert_eq!(plain.find(crib), Some(40));
+
+        for offset in [Some(40), None] {
+            let solution = crib_attack(&cipher, crib, 3, offset, &ng_score).unwrap();
+            assert!(solution.key == key());
+            assert_eq!(solution.plain, plain);
+        }
+
+        assert!(crib_attack(&cipher, "ITWAS", 3, None, &ng_score).is_err());
+    }
+}
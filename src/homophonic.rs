@@ -0,0 +1,243 @@
+extern crate rand;
+
+use rand::{Rng, RngCore};
+use rand::seq::SliceRandom;
+use rand::distributions::{Distribution, WeightedIndex};
+
+use crate::fitness::Fitness;
+use crate::freq::ENGLISH_FREQS;
+use crate::score::{NgramScore4, WordListScore};
+use crate::simann::KeySpace;
+
+const ALPHABET : &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+// Default fitness, without the unigram chi-squared penalty the solver collapses onto a few common letters
+pub const DEFAULT_FITNESS : &str = "blend,chi:1";
+
+// Fraction of neighbour moves that swap two symbols instead of reassigning one
+const SWAP_PROB : f64 = 0.2;
+
+// Cipher text over an alphabet of arbitrary symbols, each symbol decrypts to one letter
+// Keys hold the letter for each symbol in the order of symbols
+pub struct Homophonic {
+    pub symbols : Vec<String>,
+    cipher : Vec<usize>,
+    dist : WeightedIndex<f64>,
+    fitness : Fitness,
+}
+
+impl Homophonic {
+    // Symbols are whitespace or comma separated tokens if there are any,
+    // otherwise width chars each, defaulting to 2 for digits and 1 for anything else
+    pub fn new(text : &str, width : Option<usize>, fitness : Fitness) -> Result<Self, String> {
+        let tokens : Vec<String> = text.split(|c : char| c.is_whitespace() || c == ',')
+            .filter(|t| !t.is_empty())
+            .map(String::from)
+            .collect();
+
+        let tokens = if width.is_none() && tokens.len() > 1 {
+            tokens
+        } else {
+            let chars : Vec<char> = tokens.concat().chars().collect();
+            let width = width.unwrap_or_else(|| {
+                if chars.iter().all(|c| c.is_ascii_digit()) { 2 } else { 1 }
+            });
+
+            if width == 0 || !chars.len().is_multiple_of(width) {
+                return Err(format!("Cipher length is not a multiple of {}", width));
+            }
+
+            chars.chunks(width).map(|c| c.iter().collect()).collect()
+        };
+
+        if tokens.is_empty() {
+            return Err(String::from("Cipher text has no symbols"));
+        }
+
+        let mut symbols = tokens.clone();
+        if symbols.iter().all(|s| s.parse::<u32>().is_ok()) {
+            symbols.sort_by_key(|s| s.parse::<u32>().unwrap());
+        } else {
+            symbols.sort();
+        }
+        symbols.dedup();
+
+        let cipher = tokens.iter()
+            .map(|t| symbols.iter().position(|s| s == t).unwrap())
+            .collect();
+
+        let dist = WeightedIndex::new(&ENGLISH_FREQS[..]).unwrap();
+
+        Ok(Homophonic { symbols, cipher, dist, fitness })
+    }
+
+    pub fn len(&self) -> usize {
+        self.cipher.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cipher.is_empty()
+    }
+
+    // Number of times each symbol appears in the cipher
+    pub fn symbol_counts(&self) -> Vec<(&str, usize)> {
+        let mut counts = vec![0; self.symbols.len()];
+
+        for s in &self.cipher {
+            counts[*s] += 1;
+        }
+
+        let mut counts : Vec<_> = self.symbols.iter()
+            .map(|s| s.as_str())
+            .zip(counts)
+            .collect();
+
+        counts.sort_by_key(|p| std::cmp::Reverse(p.1));
+
+        counts
+    }
+
+    // The cipher symbols that decrypt to each letter
+    pub fn groups(&self, key : &str) -> Vec<(char, Vec<&str>)> {
+        ALPHABET.chars()
+            .map(|c| {
+                let syms = key.chars().zip(&self.symbols)
+                    .filter(|p| p.0 == c)
+                    .map(|p| p.1.as_str())
+                    .collect();
+                (c, syms)
+            })
+            .filter(|g : &(char, Vec<&str>)| !g.1.is_empty())
+            .collect()
+    }
+
+    // The key indexed by symbol number, as encrypt and decrypt use it, with ? for numbers that do
+    // not appear in the cipher. None unless every symbol is a number
+    pub fn indexed_key(&self, key : &str) -> Option<String> {
+        let numbers = self.symbols.iter()
+            .map(|s| s.parse::<usize>().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut indexed = vec![b'?'; numbers.iter().max().map_or(0, |m| m + 1)];
+
+        for (n, c) in numbers.iter().zip(key.bytes()) {
+            indexed[*n] = c;
+        }
+
+        Some(String::from_utf8(indexed).unwrap())
+    }
+
+    pub fn format_groups(&self, key : &str) -> String {
+        self.groups(key).iter()
+            .map(|g| format!("{}: {}", g.0, g.1.join(" ")))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// A key for nsymbols two digit symbols, every letter gets one and the rest are given out by
+// English frequency
+pub fn random_key(nsymbols : usize, rng : &mut dyn RngCore) -> String {
+    assert!((26..=100).contains(&nsymbols), "Need between 26 and 100 symbols");
+
+    let dist = WeightedIndex::new(&ENGLISH_FREQS[..]).unwrap();
+    let mut key : Vec<u8> = Vec::from(ALPHABET);
+
+    while key.len() < nsymbols {
+        key.push(b'A' + dist.sample(rng) as u8);
+    }
+
+    key.shuffle(rng);
+
+    String::from_utf8(key).unwrap()
+}
+
+// Each letter becomes one of its symbols at random, written as the two digit index in the key
+pub fn encrypt(plain : &str, key : &str, rng : &mut dyn RngCore) -> String {
+    let homophones : Vec<Vec<usize>> = ALPHABET.bytes()
+        .map(|c| key.bytes().enumerate().filter(|p| p.1 == c).map(|p| p.0).collect())
+        .collect();
+
+    plain.to_uppercase().bytes()
+        .filter(|b| b.is_ascii_uppercase())
+        .map(|b| format!("{:02}", homophones[(b - b'A') as usize].choose(rng).unwrap()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Inverse of encrypt, symbols are read as indices into the key
+pub fn decrypt(cipher : &str, key : &str) -> Result<String, String> {
+    cipher.split(|c : char| c.is_whitespace() || c == ',')
+        .filter(|t| !t.is_empty())
+        .map(|t| t.parse::<usize>().ok()
+            .and_then(|i| key.as_bytes().get(i))
+            .map(|b| *b as char)
+            .ok_or_else(|| format!("Symbol {} is not in the key", t)))
+        .collect()
+}
+
+impl KeySpace for Homophonic {
+    // Letters are drawn with English frequencies so common letters start with more homophones
+    fn generate_key(&self, rng : &mut dyn RngCore) -> String {
+        (0..self.symbols.len())
+            .map(|_| (b'A' + self.dist.sample(rng) as u8) as char)
+            .collect()
+    }
+
+    fn random_swap_key(&self, key : &str, rng : &mut dyn RngCore) -> String {
+        let mut key = Vec::from(key);
+
+        if rng.gen::<f64>() < SWAP_PROB && key.len() > 1 {
+            let idxs = rand::seq::index::sample(rng, key.len(), 2);
+            key.swap(idxs.index(0), idxs.index(1));
+        } else {
+            // Reassign a single symbol to a different letter
+            let i = rng.gen_range(0, key.len());
+            let c = b'A' + rng.gen_range(0, 25);
+            key[i] = if c >= key[i] { c + 1 } else { c };
+        }
+
+        String::from_utf8(key).unwrap()
+    }
+
+    fn decrypt(&self, key : &str) -> String {
+        let key = key.as_bytes();
+
+        self.cipher.iter()
+            .map(|s| key[*s] as char)
+            .collect()
+    }
+
+    fn score(&self, ng_score : &NgramScore4, wl_score : &WordListScore, text : &str) -> f64 {
+        self.fitness.score(ng_score, wl_score, text, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        // Two symbols for every letter, so encrypt picks between them
+        let key = format!("{}{}", ALPHABET, ALPHABET);
+        let cipher = encrypt("Meet me at noon", &key, &mut rand::thread_rng());
+
+        assert_eq!(cipher.split(' ').count(), 12);
+        assert_eq!(decrypt(&cipher, &key).unwrap(), "MEETMEATNOON");
+        assert_eq!(decrypt("12 52", &key), Err(String::from("Symbol 52 is not in the key")));
+    }
+
+    #[test]
+    fn new_reads_symbols() {
+        let fitness = || Fitness::create(DEFAULT_FITNESS, "", "").unwrap();
+
+        let keyspace = Homophonic::new("12 07 12,33", None, fitness()).unwrap();
+        assert_eq!(keyspace.symbols, vec!["07", "12", "33"]);
+        assert_eq!(keyspace.len(), 4);
+
+        assert_eq!(Homophonic::new("120712", None, fitness()).unwrap().symbols, vec!["07", "12"]);
+        assert!(Homophonic::new("12071", None, fitness()).is_err());
+        assert!(Homophonic::new(" \n", None, fitness()).is_err());
+    }
+}
@@ -0,0 +1,417 @@
+extern crate fnv;
+
+use fnv::FnvHashSet;
+
+use crate::substitution::ALPHABET;
+
+// Keyword mixed alphabets, the distinct letters of a keyword followed by the rest of the alphabet in order
+
+// Letters of a partial key or alphabet that are not known yet
+pub const UNKNOWN : u8 = b'?';
+
+// Widths tried for keyword alphabets written in rows and read off by columns
+const MAX_WIDTH : usize = 13;
+
+// Letters of a word as they go into a keyword alphabet, J is I for alphabets without J
+fn keyword_letters<'a>(word : &'a str, alphabet : &'a str) -> impl Iterator<Item = char> + 'a {
+    let merge_j = !alphabet.contains('J');
+
+    word.chars()
+        .map(|c| c.to_ascii_uppercase())
+        .map(move |c| if merge_j && c == 'J' { 'I' } else { c })
+        .filter(move |c| alphabet.contains(*c))
+}
+
+// Distinct keyword letters appended to prefix
+fn extend_distinct(prefix : &str, word : &str, alphabet : &str) -> String {
+    let mut out = String::from(prefix);
+
+    for c in keyword_letters(word, alphabet) {
+        if !out.contains(c) {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+pub fn keyword_alphabet(keyword : &str, alphabet : &str) -> String {
+    extend_distinct(&extend_distinct("", keyword, alphabet), alphabet, alphabet)
+}
+
+// Length of the shortest keyword that generates mixed, everything after it is in alphabet order
+// Unknown letters are skipped so this is a lower bound for partial alphabets
+pub fn keyword_len(mixed : &str) -> usize {
+    let b = mixed.as_bytes();
+    let mut start = b.len();
+    let mut next = None;
+
+    while start > 0 {
+        let c = b[start - 1];
+
+        if c != UNKNOWN {
+            if next.map(|n| c >= n).unwrap_or(false) {
+                break;
+            }
+
+            next = Some(c);
+        }
+
+        start -= 1;
+    }
+
+    start
+}
+
+pub fn shortest_keyword(mixed : &str) -> &str {
+    &mixed[..keyword_len(mixed)]
+}
+
+// Phrases of up to max_words dictionary words that generate mixed, shortest first
+pub fn dictionary_keywords(mixed : &str, alphabet : &str, dict : &[String],
+    max_words : usize, max_results : usize) -> Vec<String> {
+
+    let mut results = Vec::new();
+    let min_len = keyword_len(mixed);
+
+    search(mixed, alphabet, dict, min_len, "", &mut Vec::new(), max_words, max_results, &mut results);
+
+    results.sort_by_key(|r| (r.len(), r.clone()));
+    results.dedup();
+    results
+}
+
+#[allow(clippy::too_many_arguments)]
+fn search<'a>(mixed : &str, alphabet : &str, dict : &'a [String], min_len : usize, prefix : &str,
+    words : &mut Vec<&'a str>, max_words : usize, max_results : usize, results : &mut Vec<String>) {
+
+    for w in dict {
+        if results.len() >= max_results {
+            return;
+        }
+
+        let next = extend_distinct(prefix, w, alphabet);
+
+        // Words that add no letters only make longer phrases
+        if next.len() == prefix.len() || !mixed.starts_with(&next) {
+            continue;
+        }
+
+        words.push(w);
+
+        if next.len() >= min_len {
+            results.push(words.join(" "));
+        } else if words.len() < max_words {
+            search(mixed, alphabet, dict, min_len, &next, words, max_words, max_results, results);
+        }
+
+        words.pop();
+    }
+}
+
+// How a keyword alphabet became a substitution key
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AlphabetKind {
+    // Keyword alphabet on the plain side over a straight cipher alphabet
+    K1,
+    // Straight plain alphabet over a keyword alphabet on the cipher side
+    K2,
+    // The same keyword alphabet on both sides, the cipher side moved along by the given places
+    K3(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Transposition {
+    None,
+    // Keyword alphabet written in rows of this width and read down the columns left to right
+    Columns(usize),
+    // The same but the columns are read in alphabetical order of their first letter
+    KeyedColumns(usize),
+}
+
+// A substitution key explained as a keyword alphabet
+pub struct KeyedAlphabet {
+    pub kind : AlphabetKind,
+    // Places the plain and cipher alphabets are moved against each other
+    pub shift : usize,
+    pub transposition : Transposition,
+    pub keyword : String,
+    // Key in the p1 format, key[plain] = cipher, with the unknown letters filled in where the
+    // keyword alphabet gives them
+    pub key : String,
+    // Letters the input key did not have
+    pub filled : usize,
+}
+
+impl std::fmt::Display for KeyedAlphabet {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?} Shift = {} Transposition = {:?} Keyword = {} Key = {} Filled = {}",
+            self.kind, self.shift, self.transposition, self.keyword, self.key, self.filled)
+    }
+}
+
+fn rotate(s : &[u8], r : usize) -> Vec<u8> {
+    s[r..].iter().chain(&s[..r]).cloned().collect()
+}
+
+fn inverse(key : &[u8]) -> Vec<u8> {
+    let mut inv = vec![UNKNOWN; 26];
+
+    for (p, c) in key.iter().enumerate() {
+        if *c != UNKNOWN {
+            inv[(c - b'A') as usize] = b'A' + p as u8;
+        }
+    }
+
+    inv
+}
+
+fn is_consistent(partial : &[u8], full : &[u8]) -> bool {
+    partial.iter().zip(full).all(|(a, b)| *a == UNKNOWN || a == b)
+}
+
+// Alphabet of a K3 key moved by shift, filled along the longest chain plain -> cipher -> ...
+fn k3_alphabet(key : &[u8], shift : usize) -> Vec<u8> {
+    let preds : FnvHashSet<u8> = key.iter().cloned().filter(|c| *c != UNKNOWN).collect();
+
+    // Chains start where no known letter maps to them, or anywhere on a closed cycle
+    let starts : Vec<u8> = ALPHABET.bytes()
+        .filter(|c| key[(c - b'A') as usize] != UNKNOWN)
+        .collect();
+
+    starts.iter()
+        .map(|start| {
+            let mut alphabet = vec![UNKNOWN; 26];
+            let mut c = *start;
+            let mut pos = 0;
+
+            while alphabet[pos] == UNKNOWN && !alphabet.contains(&c) {
+                alphabet[pos] = c;
+                c = key[(c - b'A') as usize];
+                pos = (pos + shift) % 26;
+
+                if c == UNKNOWN {
+                    break;
+                }
+            }
+
+            (preds.contains(start), alphabet)
+        })
+        .max_by_key(|(has_pred, alphabet)| {
+            (alphabet.iter().filter(|c| **c != UNKNOWN).count(), !has_pred)
+        })
+        .map(|p| p.1)
+        .unwrap_or_else(|| vec![UNKNOWN; 26])
+}
+
+// The alphabet a key is made from for each kind, before any shift
+fn base_alphabet(kind : AlphabetKind, key : &[u8]) -> Vec<u8> {
+    match kind {
+        AlphabetKind::K1 => inverse(key),
+        AlphabetKind::K2 => key.to_vec(),
+        AlphabetKind::K3(shift) => k3_alphabet(key, shift)
+    }
+}
+
+fn alphabet_key(kind : AlphabetKind, shift : usize, alphabet : &[u8]) -> Vec<u8> {
+    let base = rotate(alphabet, (26 - shift) % 26);
+
+    match kind {
+        AlphabetKind::K1 => inverse(&base),
+        AlphabetKind::K2 => base,
+        AlphabetKind::K3(s) => {
+            let mut key = vec![UNKNOWN; 26];
+
+            for i in 0..26 {
+                if alphabet[i] != UNKNOWN {
+                    key[(alphabet[i] - b'A') as usize] = alphabet[(i + s) % 26];
+                }
+            }
+
+            key
+        }
+    }
+}
+
+fn kinds() -> Vec<AlphabetKind> {
+    let mut kinds = vec![AlphabetKind::K1, AlphabetKind::K2];
+
+    // Shifts sharing a factor with 26 split the key into several chains that cannot be lined up
+    kinds.extend((1..26).filter(|s| s % 2 == 1 && *s != 13).map(AlphabetKind::K3));
+
+    kinds
+}
+
+// Reads an alphabet written in rows of width w down the columns
+fn transpose(rows : &[u8], w : usize, keyed : bool) -> Option<Vec<u8>> {
+    let mut cols : Vec<usize> = (0..w).collect();
+
+    if keyed {
+        if rows[..w].contains(&UNKNOWN) {
+            return None;
+        }
+
+        cols.sort_by_key(|c| rows[*c]);
+    }
+
+    Some(cols.iter()
+        .flat_map(|c| rows.iter().skip(*c).step_by(w).cloned())
+        .collect())
+}
+
+// Inverse of transpose for columns read left to right
+fn untranspose(alphabet : &[u8], w : usize) -> Vec<u8> {
+    let mut rows = vec![UNKNOWN; alphabet.len()];
+    let mut it = alphabet.iter();
+
+    for c in 0..w {
+        for r in (c..alphabet.len()).step_by(w) {
+            rows[r] = *it.next().unwrap();
+        }
+    }
+
+    rows
+}
+
+// Fills a partial keyword alphabet from the shortest keyword it can have, returning the keyword
+// length. The rest of the alphabet is in order so a run of unknown letters in it is only filled when
+// the letters that are not known anywhere fit it exactly, the keyword is only filled if one letter
+// of it is missing
+fn complete(alphabet : &[u8]) -> Option<(usize, Vec<u8>)> {
+    let free : Vec<u8> = ALPHABET.bytes().filter(|c| !alphabet.contains(c)).collect();
+
+    (keyword_len(std::str::from_utf8(alphabet).unwrap())..=alphabet.len()).find_map(|p| {
+        let mut full = alphabet.to_vec();
+        let mut placed = Vec::new();
+        let mut fit = FnvHashSet::default();
+        let mut i = p;
+
+        while i < full.len() {
+            if full[i] != UNKNOWN {
+                i += 1;
+                continue;
+            }
+
+            let end = (i..full.len()).find(|j| full[*j] != UNKNOWN).unwrap_or(full.len());
+            let lo = if i > p { full[i - 1] } else { 0 };
+            let hi = if end < full.len() { full[end] } else { u8::MAX };
+            let fits : Vec<u8> = free.iter().cloned().filter(|c| *c > lo && *c < hi).collect();
+
+            if fits.len() < end - i {
+                return None;
+            }
+
+            if fits.len() == end - i {
+                full[i..end].copy_from_slice(&fits);
+                placed.extend(&fits);
+            }
+
+            fit.extend(fits);
+            i = end;
+        }
+
+        let head_unknown = full[..p].iter().filter(|c| **c == UNKNOWN).count();
+
+        // Letters that fit nowhere in the rest of the alphabet must be in the keyword
+        if free.iter().filter(|c| !fit.contains(*c)).count() > head_unknown {
+            return None;
+        }
+
+        let left : Vec<u8> = free.iter().cloned().filter(|c| !placed.contains(c)).collect();
+
+        if head_unknown == 1 && left.len() == 1 {
+            let pos = full[..p].iter().position(|c| *c == UNKNOWN).unwrap();
+            full[pos] = left[0];
+        }
+
+        Some((p, full))
+    })
+}
+
+// Explains a full or partial key, UNKNOWN for letters not known, as a keyword alphabet with
+// keywords of at most max_keyword letters, or made of a dictionary word, shortest keyword first
+pub fn analyse_key(key : &str, dict : &[String], max_keyword : usize) -> Vec<KeyedAlphabet> {
+    let key = key.to_uppercase().into_bytes();
+    assert_eq!(key.len(), 26, "Key must have 26 letters");
+
+    let known = key.iter().filter(|c| **c != UNKNOWN).count();
+    let mut results = Vec::new();
+
+    let mut push = |kind, shift, transposition, keyword : &[u8], alphabet : &[u8]| {
+        let full_key = alphabet_key(kind, shift, alphabet);
+
+        if is_consistent(&key, &full_key) {
+            let filled = full_key.iter().filter(|c| **c != UNKNOWN).count() - known;
+
+            results.push(KeyedAlphabet {
+                kind,
+                shift: match kind { AlphabetKind::K3(_) => 0, _ => (26 - shift) % 26 },
+                transposition,
+                keyword: String::from_utf8(keyword.to_vec()).unwrap(),
+                key: String::from_utf8(full_key).unwrap(),
+                filled,
+            });
+        }
+    };
+
+    let alphabets : Vec<(AlphabetKind, usize, Vec<u8>)> = kinds().into_iter()
+        .flat_map(|kind| {
+            let base = base_alphabet(kind, &key);
+            (0..26).map(move |shift| (kind, shift, rotate(&base, shift)))
+        })
+        .collect();
+
+    for (kind, shift, alphabet) in &alphabets {
+        let rows = std::iter::once((Transposition::None, alphabet.clone()))
+            .chain((2..=MAX_WIDTH).map(|w| (Transposition::Columns(w), untranspose(alphabet, w))));
+
+        for (transposition, rows) in rows {
+            if keyword_len(std::str::from_utf8(&rows).unwrap()) > max_keyword {
+                continue;
+            }
+
+            if let Some((len, full)) = complete(&rows) {
+                let keyword = &full[..len];
+
+                if len > max_keyword {
+                    continue;
+                }
+
+                let full = match transposition {
+                    Transposition::Columns(w) => transpose(&full, w, false).unwrap(),
+                    _ => full.clone()
+                };
+
+                push(*kind, *shift, transposition, keyword, &full);
+            }
+        }
+    }
+
+    // Dictionary words are tried as keywords with every transposition
+    for word in dict {
+        let rows = keyword_alphabet(word, ALPHABET).into_bytes();
+        let mut forms = vec![(Transposition::None, rows.clone())];
+
+        for w in 2..=MAX_WIDTH {
+            forms.push((Transposition::Columns(w), transpose(&rows, w, false).unwrap()));
+            forms.push((Transposition::KeyedColumns(w), transpose(&rows, w, true).unwrap()));
+        }
+
+        for (transposition, full) in &forms {
+            for (kind, shift, alphabet) in &alphabets {
+                if is_consistent(alphabet, full) {
+                    push(*kind, *shift, *transposition, word.as_bytes(), full);
+                }
+            }
+        }
+    }
+
+    // Most complete keys first, then the shortest keyword for each key as every rotation of an
+    // alphabet explains the same key
+    results.sort_by_key(|r| (std::cmp::Reverse(r.filled), r.keyword.len(), r.keyword.clone()));
+
+    let mut seen = FnvHashSet::default();
+    results.retain(|r| seen.insert(r.key.clone()));
+
+    results
+}
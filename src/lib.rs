@@ -1,3 +1,23 @@
 pub mod score;
 pub mod freq;
 pub mod simann;
+pub mod crctable;
+pub mod crcforge;
+pub mod homophonic;
+pub mod text;
+pub mod pattern;
+pub mod segment;
+pub mod fitness;
+pub mod substitution;
+pub mod playfair;
+pub mod keyword;
+pub mod session;
+pub mod solvers;
+pub mod distrib;
+pub mod stop;
+pub mod calibrate;
+pub mod cancel;
+pub mod threads;
+pub mod fractionated;
+pub mod foursquare;
+pub mod hill;
@@ -0,0 +1,263 @@
+extern crate fnv;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufRead;
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+use crate::freq::ENGLISH_FREQS;
+use crate::score::NgramScore4;
+
+const ALPHABET : &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+// Letter pattern of a word, each new letter gets the next letter of the alphabet, PEOPLE -> ABCADB
+pub fn pattern(word : &str) -> String {
+    let mut seen = Vec::<u8>::with_capacity(word.len());
+
+    word.bytes()
+        .map(|b| {
+            let idx = match seen.iter().position(|s| *s == b) {
+                Some(idx) => idx,
+                None => {
+                    seen.push(b);
+                    seen.len() - 1
+                }
+            };
+            (b'A' + idx as u8) as char
+        })
+        .collect()
+}
+
+// Dictionary words grouped by letter pattern
+pub struct PatternDict {
+    pattern_map : FnvHashMap<String, Vec<String>>,
+}
+
+impl PatternDict {
+    pub fn create(filename: &str) -> Self {
+        let file = File::open(filename)
+            .unwrap_or_else(|_| panic!("Cannot open {}", filename));
+
+        let file = BufReader::new(file);
+
+        // The word lists skip single letters, only A and I are words by themselves
+        let mut word_set : FnvHashSet<String> = file.lines()
+            .map(|l| l.unwrap())
+            .filter_map(|l| l.split_ascii_whitespace().next().map(|w| w.to_uppercase()))
+            .filter(|w| w.bytes().all(|b| b.is_ascii_uppercase()))
+            .collect();
+
+        word_set.insert(String::from("A"));
+        word_set.insert(String::from("I"));
+
+        let mut pattern_map = FnvHashMap::<String, Vec<String>>::default();
+
+        for w in word_set {
+            pattern_map.entry(pattern(&w)).or_default().push(w);
+        }
+
+        for v in pattern_map.values_mut() {
+            v.sort();
+        }
+
+        PatternDict { pattern_map }
+    }
+
+    pub fn words(&self, pattern : &str) -> &[String] {
+        self.pattern_map.get(pattern).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+}
+
+pub struct PatternConfig {
+    // Cipher words allowed to have no dictionary match, for names and rare words
+    pub max_skips : usize,
+    // Search nodes visited before giving up
+    pub max_nodes : u64,
+    pub max_solutions : usize,
+}
+
+// A substitution key in the same format as p1, key[plain letter] = cipher letter, with its decrypt
+pub struct PatternResult {
+    pub key : String,
+    pub decrypt : String,
+    pub score : f64,
+    // Cipher letters fixed by dictionary words, the rest were filled in by frequency
+    pub known : usize,
+}
+
+struct Search<'a> {
+    words : Vec<(&'a [u8], &'a [String])>,
+    c2p : [u8; 26],
+    p2c : [u8; 26],
+    nodes : u64,
+    skips : usize,
+    config : &'a PatternConfig,
+    solutions : FnvHashSet<[u8; 26]>,
+}
+
+impl<'a> Search<'a> {
+    fn consistent(&self, cw : &[u8], pw : &[u8]) -> bool {
+        cw.iter().zip(pw).all(|(c, p)| {
+            let (c, p) = ((c - b'A') as usize, (p - b'A') as usize);
+
+            match self.c2p[c] {
+                0 => self.p2c[p] == 0,
+                m => m as usize - 1 == p
+            }
+        })
+    }
+
+    // Maps the new letters of the word, returning the cipher letters that were assigned
+    fn assign(&mut self, cw : &[u8], pw : &[u8]) -> Vec<usize> {
+        let mut assigned = Vec::new();
+
+        for (c, p) in cw.iter().zip(pw) {
+            let (c, p) = ((c - b'A') as usize, (p - b'A') as usize);
+
+            if self.c2p[c] == 0 {
+                self.c2p[c] = p as u8 + 1;
+                self.p2c[p] = c as u8 + 1;
+                assigned.push(c);
+            }
+        }
+
+        assigned
+    }
+
+    fn unassign(&mut self, assigned : &[usize]) {
+        for c in assigned {
+            let p = self.c2p[*c] as usize - 1;
+            self.c2p[*c] = 0;
+            self.p2c[p] = 0;
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.nodes >= self.config.max_nodes || self.solutions.len() >= self.config.max_solutions
+    }
+
+    // Depth first search, always extending the cipher word with the fewest consistent candidates
+    fn search(&mut self, remaining : &mut Vec<usize>) {
+        self.nodes += 1;
+
+        if self.done() {
+            return;
+        }
+
+        if remaining.is_empty() {
+            self.solutions.insert(self.c2p);
+            return;
+        }
+
+        let (pos, _) = remaining.iter().enumerate()
+            .map(|(pos, w)| {
+                let (cw, cands) = self.words[*w];
+                (pos, cands.iter().filter(|pw| self.consistent(cw, pw.as_bytes())).count())
+            })
+            .min_by_key(|p| p.1)
+            .unwrap();
+
+        let w = remaining.swap_remove(pos);
+        let (cw, cands) = self.words[w];
+
+        for pw in cands {
+            if self.done() {
+                break;
+            }
+
+            if !self.consistent(cw, pw.as_bytes()) {
+                continue;
+            }
+
+            let assigned = self.assign(cw, pw.as_bytes());
+            self.search(remaining);
+            self.unassign(&assigned);
+        }
+
+        // The word may not be in the dictionary even if some candidates fit
+        if self.skips < self.config.max_skips && !self.done() {
+            self.skips += 1;
+            self.search(remaining);
+            self.skips -= 1;
+        }
+
+        remaining.push(w);
+        let last = remaining.len() - 1;
+        remaining.swap(pos, last);
+    }
+}
+
+// Fills the unknown letters of a partial mapping, most frequent cipher letters first
+fn complete_key(c2p : &[u8; 26], cipher : &str) -> String {
+    let mut c2p = *c2p;
+    let mut counts = [0; 26];
+    for b in cipher.bytes() {
+        counts[(b - b'A') as usize] += 1;
+    }
+
+    let mut free_c : Vec<usize> = (0..26).filter(|c| c2p[*c] == 0).collect();
+    free_c.sort_by_key(|c| std::cmp::Reverse(counts[*c]));
+
+    let mut free_p : Vec<usize> = (0..26).filter(|p| !c2p.contains(&(*p as u8 + 1))).collect();
+    free_p.sort_by(|a, b| ENGLISH_FREQS[*b].partial_cmp(&ENGLISH_FREQS[*a]).unwrap());
+
+    for (c, p) in free_c.iter().zip(free_p) {
+        c2p[*c] = p as u8 + 1;
+    }
+
+    let mut key = [0; 26];
+    for (c, p) in c2p.iter().enumerate() {
+        key[*p as usize - 1] = b'A' + c as u8;
+    }
+
+    String::from_utf8(key.to_vec()).unwrap()
+}
+
+fn decrypt(cipher : &str, key : &str) -> String {
+    cipher.chars()
+        .map(|c| ALPHABET.as_bytes()[key.find(c).unwrap()] as char)
+        .collect()
+}
+
+// Builds substitution keys where every cipher word (bar max_skips of them) decrypts to a
+// dictionary word with the same letter pattern, best quadgram score first
+pub fn pattern_attack(cipher : &str, cipher_words : &[&str], dict : &PatternDict,
+    ng_score : &NgramScore4, config : &PatternConfig) -> Vec<PatternResult> {
+
+    let mut unique : Vec<&str> = cipher_words.to_vec();
+    unique.sort();
+    unique.dedup();
+
+    let words : Vec<(&[u8], &[String])> = unique.iter()
+        .map(|w| (w.as_bytes(), dict.words(&pattern(w))))
+        .collect();
+
+    let mut search = Search {
+        words,
+        c2p: [0; 26],
+        p2c: [0; 26],
+        nodes: 0,
+        skips: 0,
+        config,
+        solutions: FnvHashSet::default(),
+    };
+
+    let mut remaining : Vec<usize> = (0..unique.len()).collect();
+    search.search(&mut remaining);
+
+    let mut results : Vec<PatternResult> = search.solutions.iter()
+        .map(|c2p| {
+            let key = complete_key(c2p, cipher);
+            let decrypt = decrypt(cipher, &key);
+            let score = ng_score.score(&decrypt);
+            let known = c2p.iter().filter(|p| **p != 0).count();
+
+            PatternResult { key, decrypt, score, known }
+        })
+        .collect();
+
+    results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+
+    results
+}
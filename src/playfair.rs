@@ -0,0 +1,232 @@
+extern crate rand;
+
+use rand::{Rng, RngCore};
+use rand::seq::SliceRandom;
+
+use crate::fitness::Fitness;
+use crate::keyword;
+use crate::score::{NgramScore4, WordListScore};
+use crate::simann::KeySpace;
+use crate::text::CipherText;
+
+// 5x5 grid alphabet, J is merged into I
+pub const PLAYFAIR_ALPHABET : &str = "ABCDEFGHIKLMNOPQRSTUVWXYZ";
+
+// Letter used to split doubled letters and pad odd length texts, and the one used for doubled X
+const NULL : u8 = b'X';
+const NULL_X : u8 = b'Q';
+
+// Upper cased letters with J merged into I, the letters of a 5x5 square
+pub fn merge_j(plain : &str) -> String {
+    plain.bytes()
+        .filter(|b| b.is_ascii_alphabetic())
+        .map(|b| match b.to_ascii_uppercase() {
+            b'J' => 'I',
+            b => b as char
+        })
+        .collect()
+}
+
+// Upper cased letters with J merged into I, doubled letters in a digraph split by X and padded
+// to an even length, ready to be encrypted
+pub fn prepare(plain : &str) -> String {
+    let letters = merge_j(plain).into_bytes();
+
+    let mut prepared = Vec::with_capacity(letters.len() + letters.len() / 8 + 1);
+
+    for b in letters {
+        if prepared.len() % 2 == 1 && *prepared.last().unwrap() == b {
+            prepared.push(if b == NULL { NULL_X } else { NULL });
+        }
+
+        prepared.push(b);
+    }
+
+    if prepared.len() % 2 == 1 {
+        prepared.push(if *prepared.last().unwrap() == NULL { NULL_X } else { NULL });
+    }
+
+    String::from_utf8(prepared).unwrap()
+}
+
+// Row and column of every letter in the key grid
+fn positions(key : &str) -> [(usize, usize); 26] {
+    let mut pos = [(0, 0); 26];
+
+    for (i, b) in key.bytes().enumerate() {
+        pos[(b - b'A') as usize] = (i / 5, i % 5);
+    }
+
+    pos
+}
+
+// Applies the Playfair rules, pairs in the same row or column move step places along it
+fn apply(text : &str, key : &str, step : usize) -> String {
+    assert_eq!(text.len() % 2, 0, "Playfair text must have an even length");
+
+    let pos = positions(key);
+    let key = key.as_bytes();
+    let mut out = String::with_capacity(text.len());
+
+    for d in text.as_bytes().chunks(2) {
+        let (r1, c1) = pos[(d[0] - b'A') as usize];
+        let (r2, c2) = pos[(d[1] - b'A') as usize];
+
+        let (a, b) = if r1 == r2 {
+            (r1 * 5 + (c1 + step) % 5, r2 * 5 + (c2 + step) % 5)
+        } else if c1 == c2 {
+            (((r1 + step) % 5) * 5 + c1, ((r2 + step) % 5) * 5 + c2)
+        } else {
+            (r1 * 5 + c2, r2 * 5 + c1)
+        };
+
+        out.push(key[a] as char);
+        out.push(key[b] as char);
+    }
+
+    out
+}
+
+// Encrypts a text already passed through prepare
+pub fn encrypt(prepared : &str, key : &str) -> String {
+    apply(prepared, key, 1)
+}
+
+// Moving 4 places along a row or column is moving 1 back
+pub fn decrypt(cipher : &str, key : &str) -> String {
+    apply(cipher, key, 4)
+}
+
+// The square a keyword generates, its distinct letters followed by the rest of the alphabet
+pub fn keyword_square(keyword : &str) -> String {
+    keyword::keyword_alphabet(keyword, PLAYFAIR_ALPHABET)
+}
+
+pub fn random_key(rng : &mut dyn RngCore) -> String {
+    let mut k = Vec::from(PLAYFAIR_ALPHABET);
+    k.shuffle(rng);
+
+    String::from_utf8(k).unwrap()
+}
+
+// The 25 keys that encrypt the same way, every cyclic shift of the rows and columns of the grid
+pub fn equivalent_keys(key : &str) -> Vec<String> {
+    let key = key.as_bytes();
+    let mut keys = Vec::with_capacity(25);
+
+    for dr in 0..5 {
+        for dc in 0..5 {
+            let k : String = (0..25)
+                .map(|i| key[((i / 5 + dr) % 5) * 5 + (i % 5 + dc) % 5] as char)
+                .collect();
+
+            keys.push(k);
+        }
+    }
+
+    keys
+}
+
+// Of the 25 equivalent squares, the one made by the shortest keyword, alphabetically first on ties
+// Transposed squares are not included, they reverse every digraph that forms a rectangle
+pub fn canonical_square(key : &str) -> String {
+    equivalent_keys(key).into_iter()
+        .min_by_key(|k| (keyword::keyword_len(k), k.clone()))
+        .unwrap()
+}
+
+// Dictionary phrases that generate any of the equivalent squares, with the square, shortest first
+pub fn dictionary_keywords(key : &str, dict : &[String], max_words : usize,
+    max_results : usize) -> Vec<(String, String)> {
+
+    let mut results : Vec<(String, String)> = equivalent_keys(key).into_iter()
+        .flat_map(|k| {
+            keyword::dictionary_keywords(&k, PLAYFAIR_ALPHABET, dict, max_words, max_results).into_iter()
+                .map(move |w| (w, k.clone()))
+        })
+        .collect();
+
+    results.sort_by_key(|r| (r.0.len(), r.0.clone()));
+    results.truncate(max_results);
+    results
+}
+
+// The annealing moves on a key laid out as a square (dims 2) or a cube (dims 3) with sides of side
+// letters, reverse the key, swap two columns, rows or layers, or swap two letters
+pub fn grid_swap_key(key : &str, side : usize, dims : usize, rng : &mut dyn RngCore) -> String {
+    let r : f64 = rng.gen();
+    let mut key = Vec::from(key);
+
+    if r > 0.95 {
+        // Reverse the Key
+        key.reverse();
+    } else if r > 0.85 {
+        // Swap two slices across one axis, columns first as their cells are next to each other
+        let axis = (((0.95 - r) / 0.10 * dims as f64) as usize).min(dims - 1);
+        let stride = side.pow(axis as u32);
+        let slices = rand::seq::index::sample(rng, side, 2).into_vec();
+
+        for i in 0..key.len() {
+            if (i / stride) % side == slices[0] {
+                key.swap(i, i - slices[0] * stride + slices[1] * stride);
+            }
+        }
+    } else {
+        let idxs = rand::seq::index::sample(rng, key.len(), 2).into_vec();
+        key.swap(idxs[0], idxs[1]);
+    }
+
+    String::from_utf8(key).unwrap()
+}
+
+pub struct Playfair {
+    pub cipher : CipherText,
+    fitness : Fitness,
+}
+
+impl Playfair {
+    pub fn new(cipher : CipherText, fitness : Fitness) -> Self {
+        Playfair { cipher, fitness }
+    }
+}
+
+impl KeySpace for Playfair {
+    fn generate_key(&self, rng : &mut dyn RngCore) -> String {
+        //String::from("EBODVGZCXUKYQHFNWSPTRMIAL")
+        random_key(rng)
+    }
+
+    fn random_swap_key(&self, key : &str, rng : &mut dyn RngCore) -> String {
+        grid_swap_key(key, 5, 2, rng)
+    }
+
+    fn decrypt(&self, key : &str) -> String {
+        decrypt(&self.cipher.letters, key)
+    }
+
+    fn score(&self, ng_score : &NgramScore4, wl_score : &WordListScore, text : &str) -> f64 {
+        self.fitness.score(ng_score, wl_score, text, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        let key = keyword_square("PLAYFAIR EXAMPLE");
+        let prepared = prepare("Hide the gold in the tree stump");
+        assert_eq!(prepared, "HIDETHEGOLDINTHETREXESTUMP");
+
+        let cipher = encrypt(&prepared, &key);
+        assert_eq!(cipher, "BMODZBXDNABEKUDMUIXMMOUVIF");
+        assert_eq!(decrypt(&cipher, &key), prepared);
+    }
+
+    #[test]
+    fn prepare_splits_doubled_x_with_q() {
+        assert_eq!(prepare("Jazz"), "IAZXZX");
+        assert_eq!(prepare("xx"), "XQXQ");
+    }
+}
@@ -4,6 +4,7 @@ use std::fs::File;
 use std::io::BufReader;
 use std::io::BufRead;
 use std::hash::{Hash, Hasher};
+use std::ops::Range;
 
 use fnv::{FnvHashMap, FnvHashSet};
 
@@ -95,7 +96,7 @@ impl Hash for NgramText2 {
 impl NgramScore2 {
     pub fn create(filename: &str) -> Self {
         let file = File::open(filename)
-            .expect(&format!("Cannot open {}", filename));
+            .unwrap_or_else(|_| panic!("Cannot open {}", filename));
 
         let file = BufReader::new(file);
 
@@ -107,7 +108,7 @@ impl NgramScore2 {
                 let n = it.next().unwrap().parse::<i64>().unwrap();
 
                 qgram.chars().for_each(|c| {
-                    assert!('A' <= c && c <= 'Z');
+                    assert!(c.is_ascii_uppercase());
                 });
                 assert_eq!(it.next(), None);
 
@@ -142,7 +143,7 @@ impl NgramScore2 {
 impl NgramScore4 {
     pub fn create(filename: &str) -> Self {
         let file = File::open(filename)
-            .expect(&format!("Cannot open {}", filename));
+            .unwrap_or_else(|_| panic!("Cannot open {}", filename));
 
         let file = BufReader::new(file);
 
@@ -154,7 +155,7 @@ impl NgramScore4 {
                 let n = it.next().unwrap().parse::<i64>().unwrap();
 
                 qgram.chars().for_each(|c| {
-                    assert!('A' <= c && c <= 'Z');
+                    assert!(c.is_ascii_uppercase());
                 });
                 assert_eq!(it.next(), None);
 
@@ -169,16 +170,40 @@ impl NgramScore4 {
         NgramScore4 { ngram_map, total }
     }
 
+    // Probability of every quadgram in the table
+    pub fn probabilities(&self) -> impl Iterator<Item = f64> + '_ {
+        self.ngram_map.values().map(move |n| *n as f64 / self.total)
+    }
+
+    // Log10 probability scored for a quadgram missing from the table
+    pub fn floor_score(&self) -> f64 {
+        (0.01 / self.total).log10()
+    }
+
+    // Number of quadgrams scored in a text of length len
+    pub fn ngram_count(len : usize) -> usize {
+        len.saturating_sub(NGRAM_LEN - 1)
+    }
+
+    // Mean score of English text of length len, the entropy of the quadgram table per quadgram
+    pub fn expected_score(&self, len : usize) -> f64 {
+        let per_ngram : f64 = self.probabilities()
+            .map(|p| p * p.log10())
+            .sum();
+
+        per_ngram * Self::ngram_count(len) as f64
+    }
+
     pub fn score(&self, s : &str) -> f64 {
         let mut score = 0.0;
 
-        for start in 0..s.len()-NGRAM_LEN+1 {
+        for start in 0..Self::ngram_count(s.len()) {
             let ngram = NgramText4::from(&s[start..start+NGRAM_LEN]);
 
             if let Some(freq) = self.ngram_map.get(&ngram) {
                 score += (*freq as f64 / self.total).log10();
             } else {
-                score += (0.01 / self.total).log10();
+                score += self.floor_score();
             }
         }
 
@@ -186,18 +211,69 @@ impl NgramScore4 {
     }
 }
 
+// Trie over the word list, node 0 is the root and 0 in next means no child
+struct WordTrie {
+    next : Vec<[u32; 26]>,
+    is_word : Vec<bool>,
+}
+
+impl WordTrie {
+    fn new() -> Self {
+        WordTrie { next: vec![[0; 26]], is_word: vec![false] }
+    }
+
+    fn insert(&mut self, w : &str) {
+        let mut node = 0;
+
+        for b in w.bytes() {
+            let c = (b - b'A') as usize;
+
+            if self.next[node][c] == 0 {
+                self.next.push([0; 26]);
+                self.is_word.push(false);
+                self.next[node][c] = (self.next.len() - 1) as u32;
+            }
+
+            node = self.next[node][c] as usize;
+        }
+
+        self.is_word[node] = true;
+    }
+
+    // Lengths of every word that s starts with
+    fn prefixes<'a>(&'a self, s : &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let mut node = 0;
+
+        s.iter()
+            .map_while(move |b| {
+                if !b.is_ascii_uppercase() {
+                    return None;
+                }
+
+                node = self.next[node][(b - b'A') as usize] as usize;
+                if node == 0 { None } else { Some(node) }
+            })
+            .enumerate()
+            .filter(move |p| self.is_word[p.1])
+            .map(|p| p.0 + 1)
+    }
+}
+
 pub struct WordListScore {
     word_set : FnvHashSet<String>,
-    max_len : usize
+    trie : WordTrie,
+    // Words of length l count as l^length_exponent when choosing which words cover the text
+    pub length_exponent : f32,
 }
 
 impl WordListScore {
     pub fn create(filename: &str) -> Self {
         let file = File::open(filename)
-            .expect(&format!("Cannot open {}", filename));
+            .unwrap_or_else(|_| panic!("Cannot open {}", filename));
 
         let file = BufReader::new(file);
 
+        // Lines may have a count after the word, it is only used by the segmenter
         let word_set : FnvHashSet<_> = file.lines()
             .map(|l| l.unwrap())
             .map(|l| {
@@ -205,42 +281,148 @@ impl WordListScore {
                 let word = it.next().unwrap().to_uppercase();
 
                 word.chars().for_each(|c| {
-                    assert!('A' <= c && c <= 'Z', format!("Bad: {}", word));
+                    assert!(c.is_ascii_uppercase(), "Bad: {}", word);
                 });
-                assert_eq!(it.next(), None);
 
                 word
             })
-            .filter(|w| (w.len() >= 2))
+            .filter(|w| w.len() >= 2)
             .collect();
 
-        let max_len = word_set.iter()
-            .map(|w| w.len())
-            .max()
-            .unwrap();
-        
-        WordListScore { word_set, max_len }
+        let mut trie = WordTrie::new();
+        for w in &word_set {
+            trie.insert(w);
+        }
+
+        WordListScore { word_set, trie, length_exponent: 1.0 }
     }
 
+    // Fraction of s covered by non overlapping words, choosing the words that maximise the
+    // total weight of covered characters
     pub fn coverage(&self, s : &str) -> f32 {
-        let mut start = 0;
-        let mut num_word_chars = 0;
-
-        while start < s.len() - 1 {
-            let rem = std::cmp::min(s.len() - start, self.max_len);
-            for l in 0..rem {
-                let word = &s[start..start+l];
-
-                if self.word_set.contains(word) {
-                    num_word_chars += word.len();
-                    start += word.len() - 1;
-                    break;
+        let s = s.as_bytes();
+        let n = s.len();
+
+        if n == 0 {
+            return 0.0;
+        }
+
+        // best[i] is the (weight, covered chars) of the best cover of s[i..]
+        let mut best = vec![(0.0f32, 0usize); n + 1];
+
+        for i in (0..n).rev() {
+            best[i] = best[i + 1];
+
+            for l in self.trie.prefixes(&s[i..]) {
+                let weight = (l as f32).powf(self.length_exponent) + best[i + l].0;
+
+                if weight > best[i].0 {
+                    best[i] = (weight, l + best[i + l].1);
                 }
             }
-
-            start += 1;
         }
 
+        best[0].1 as f32 / n as f32
+    }
+
+    // Like coverage but only counts whole words at known word boundaries
+    pub fn word_coverage(&self, s : &str, words : &[Range<usize>]) -> f32 {
+        let num_word_chars : usize = words.iter()
+            .map(|w| &s[w.clone()])
+            .filter(|w| self.is_word(w))
+            .map(|w| w.len())
+            .sum();
+
         num_word_chars as f32 / s.len() as f32
     }
+
+    // The word list skips single letters, only A and I are words by themselves
+    pub fn is_word(&self, w : &str) -> bool {
+        match w.len() {
+            1 => w == "A" || w == "I",
+            _ => self.word_set.contains(w)
+        }
+    }
+}
+
+// Quadgram score scaled down by how much of the text is made of known words
+pub fn score_text(ng_score: &NgramScore4, wl_score: &WordListScore, text : &str) -> f64 {
+    let score = ng_score.score(text);
+    let word_coverage = wl_score.coverage(text);
+
+    score * 0.5 + 0.5 * score * (1.0 - word_coverage) as f64
+}
+
+// Same as score_text but with word hits at the word boundaries of the cipher
+pub fn score_text_words(ng_score: &NgramScore4, wl_score: &WordListScore,
+    text : &str, words : &[Range<usize>]) -> f64 {
+    let score = ng_score.score(text);
+    let word_coverage = wl_score.word_coverage(text, words);
+
+    score * 0.5 + 0.5 * score * (1.0 - word_coverage) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_list(words : &[&str]) -> WordListScore {
+        let word_set : FnvHashSet<String> = words.iter().map(|w| String::from(*w)).collect();
+
+        let mut trie = WordTrie::new();
+        for w in &word_set {
+            trie.insert(w);
+        }
+
+        WordListScore { word_set, trie, length_exponent: 1.0 }
+    }
+
+    #[test]
+    fn coverage_prefers_the_longer_cover() {
+        let wl = word_list(&["THE", "REF", "THEREFORE"]);
+        assert_eq!(wl.coverage("THEREFORE"), 1.0);
+
+        let wl = word_list(&["THE", "THERE", "FOR"]);
+        assert_eq!(wl.coverage("THEREFORE"), 8.0 / 9.0);
+    }
+
+    #[test]
+    fn coverage_counts_words_ending_the_text() {
+        let wl = word_list(&["THE", "END"]);
+        assert_eq!(wl.coverage("THE"), 1.0);
+        assert_eq!(wl.coverage("XXEND"), 3.0 / 5.0);
+        assert_eq!(wl.coverage("THEEND"), 1.0);
+    }
+
+    #[test]
+    fn coverage_without_words() {
+        let wl = word_list(&["THE"]);
+        assert_eq!(wl.coverage("XQZJ"), 0.0);
+        assert_eq!(wl.coverage("TH"), 0.0);
+        assert_eq!(wl.coverage(""), 0.0);
+    }
+
+    #[test]
+    fn length_exponent_favours_long_words() {
+        let mut wl = word_list(&["AB", "BCD", "DE"]);
+        assert_eq!(wl.coverage("ABCDE"), 4.0 / 5.0);
+
+        wl.length_exponent = 2.0;
+        assert_eq!(wl.coverage("ABCDE"), 3.0 / 5.0);
+    }
+
+    #[test]
+    fn coverage_of_a_sentence() {
+        // WE THEREFORE HOLD THESE TRUTHS TO BE SELF EVIDENT, with shorter words inside the long ones
+        let sentence = "WETHEREFOREHOLDTHESETRUTHSTOBESELFEVIDENT";
+        let words = ["WE", "THE", "THERE", "FOR", "HOLD", "THESE", "TRUTH", "TRUTHS", "TO", "BE", "SELF", "ELF",
+            "EVIDENT"];
+
+        // Without THEREFORE the best cover is THERE and FOR, leaving its last E
+        let wl = word_list(&words);
+        assert_eq!(wl.coverage(sentence), 40.0 / 41.0);
+
+        let wl = word_list(&[&words[..], &["THEREFORE"]].concat());
+        assert_eq!(wl.coverage(sentence), 1.0);
+    }
 }
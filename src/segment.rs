@@ -0,0 +1,165 @@
+extern crate fnv;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufRead;
+
+use fnv::FnvHashMap;
+
+// Weight of the unigram probability when a bigram was never seen, stupid backoff
+const BACKOFF : f64 = 0.4;
+
+// A text split into words with the log10 probability of that split
+pub struct Segmentation {
+    pub words : Vec<String>,
+    pub log_prob : f64,
+}
+
+impl std::fmt::Display for Segmentation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.words.join(" "))
+    }
+}
+
+// Viterbi word segmenter over unigram and optionally bigram word counts
+pub struct Segmenter {
+    unigrams : FnvHashMap<String, f64>,
+    bigrams : FnvHashMap<String, f64>,
+    total : f64,
+    min_count : f64,
+    max_len : usize,
+}
+
+// Lines are WORD or WORD COUNT, words without a count are counted once
+fn read_counts(filename : &str, nwords : usize) -> Vec<(String, f64)> {
+    let file = File::open(filename)
+        .unwrap_or_else(|_| panic!("Cannot open {}", filename));
+
+    BufReader::new(file).lines()
+        .map(|l| l.unwrap())
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            let it : Vec<&str> = l.split_ascii_whitespace().collect();
+            assert!(it.len() == nwords || it.len() == nwords + 1, "Bad line: {}", l);
+
+            let words = it[..nwords].join(" ").to_uppercase();
+            let n = it.get(nwords).map(|n| n.parse::<f64>().unwrap()).unwrap_or(1.0);
+
+            (words, n)
+        })
+        .collect()
+}
+
+impl Segmenter {
+    pub fn create(filename : &str) -> Self {
+        let mut unigrams : FnvHashMap<String, f64> = read_counts(filename, 1).into_iter()
+            .filter(|p| p.0.bytes().all(|b| b.is_ascii_uppercase()))
+            .collect();
+
+        // The word lists skip single letters, only A and I are words by themselves
+        for w in &["A", "I"] {
+            unigrams.entry(String::from(*w)).or_insert(1.0);
+        }
+
+        let total = unigrams.values().sum();
+        let min_count = unigrams.values().cloned().fold(f64::INFINITY, f64::min);
+        let max_len = unigrams.keys().map(|w| w.len()).max().unwrap();
+
+        Segmenter { unigrams, bigrams: FnvHashMap::default(), total, min_count, max_len }
+    }
+
+    // Adds W1 W2 COUNT bigram counts, probabilities are conditional on the first word
+    pub fn with_bigrams(mut self, filename : &str) -> Self {
+        self.bigrams = read_counts(filename, 2).into_iter().collect();
+        self
+    }
+
+    // The word list, with the bigram counts from --word-bigrams FILE when given, which is taken
+    // out of the arguments
+    pub fn from_args(args : &mut Vec<String>) -> Self {
+        let segmenter = Segmenter::create("wordlist.txt");
+
+        match args.iter().position(|a| a == "--word-bigrams") {
+            Some(i) => {
+                let filename = args.get(i + 1).cloned().expect("--word-bigrams needs a file");
+                args.drain(i..=i + 1);
+
+                segmenter.with_bigrams(&filename)
+            },
+            None => segmenter
+        }
+    }
+
+    // Unknown words are less likely than the rarest known word, each letter costing as much as
+    // a word so known words are split out of unknown runs
+    fn word_prob(&self, w : &str) -> f64 {
+        match self.unigrams.get(w) {
+            Some(c) => (c / self.total).log10(),
+            None => (self.min_count / self.total).log10() - w.len() as f64 * self.total.log10().max(1.0)
+        }
+    }
+
+    fn cond_prob(&self, prev : &str, w : &str, word_prob : f64) -> f64 {
+        match (self.bigrams.get(&format!("{} {}", prev, w)), self.unigrams.get(prev)) {
+            (Some(c12), Some(c1)) => (c12 / c1.max(*c12)).log10(),
+            _ => BACKOFF.log10() + word_prob
+        }
+    }
+
+    pub fn segment(&self, text : &str) -> Segmentation {
+        let n = text.len();
+
+        if n == 0 {
+            return Segmentation { words: Vec::new(), log_prob: 0.0 };
+        }
+
+        // best[i][l] is the best split of text[..i] ending in a word of length l, with the
+        // length of the word before it, only l = 0 is used at i = 0
+        let mut best = vec![vec![(f64::NEG_INFINITY, 0); self.max_len + 1]; n + 1];
+        best[0][0] = (0.0, 0);
+
+        for i in 1..=n {
+            for l in 1..=std::cmp::min(self.max_len, i) {
+                let j = i - l;
+                let w = &text[j..i];
+                let word_prob = self.word_prob(w);
+
+                for pl in 0..=std::cmp::min(self.max_len, j) {
+                    let (prev_score, _) = best[j][pl];
+
+                    if prev_score == f64::NEG_INFINITY {
+                        continue;
+                    }
+
+                    let p = if pl == 0 || self.bigrams.is_empty() {
+                        word_prob
+                    } else {
+                        self.cond_prob(&text[j - pl..j], w, word_prob)
+                    };
+
+                    if prev_score + p > best[i][l].0 {
+                        best[i][l] = (prev_score + p, pl);
+                    }
+                }
+            }
+        }
+
+        let (mut l, log_prob) = (1..=std::cmp::min(self.max_len, n))
+            .map(|l| (l, best[n][l].0))
+            .fold((0, f64::NEG_INFINITY), |a, b| if b.1 > a.1 { b } else { a });
+
+        let mut words = Vec::new();
+        let mut i = n;
+
+        while i > 0 {
+            words.push(String::from(&text[i - l..i]));
+            let pl = best[i][l].1;
+            i -= l;
+            l = pl;
+        }
+
+        words.reverse();
+
+        Segmentation { words, log_prob }
+    }
+}
@@ -0,0 +1,184 @@
+extern crate rand;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+use rand::RngCore;
+
+use crate::score::{NgramScore4, WordListScore};
+use crate::simann::KeySpace;
+use crate::substitution::{Substitution, ALPHABET};
+
+// Swaps of unlocked letters applied to the current key to start each annealing run
+const START_SWAPS : usize = 3;
+
+// A substitution key being fixed by hand, in the p1 format key[plain] = cipher, with the plain
+// letters whose mapping is locked and the previous states for undo
+pub struct Session {
+    pub key : Vec<u8>,
+    pub locked : [bool; 26],
+    history : Vec<(Vec<u8>, [bool; 26])>,
+}
+
+fn letter(c : char) -> Result<usize, String> {
+    let c = c.to_ascii_uppercase();
+
+    if c.is_ascii_uppercase() {
+        Ok((c as u8 - b'A') as usize)
+    } else {
+        Err(format!("{} is not a letter", c))
+    }
+}
+
+impl Session {
+    pub fn new(key : &str) -> Self {
+        Session { key: Vec::from(key), locked: [false; 26], history: Vec::new() }
+    }
+
+    pub fn key(&self) -> &str {
+        std::str::from_utf8(&self.key).unwrap()
+    }
+
+    fn plain_of(&self, cipher : usize) -> usize {
+        self.key.iter().position(|c| (c - b'A') as usize == cipher).unwrap()
+    }
+
+    fn checkpoint(&mut self) {
+        self.history.push((self.key.clone(), self.locked));
+    }
+
+    // Makes cipher letter c decrypt to plain letter p, the cipher letter p had moves to the old plain of c
+    pub fn assign(&mut self, c : char, p : char) -> Result<(), String> {
+        let (c, p) = (letter(c)?, letter(p)?);
+        let old_p = self.plain_of(c);
+
+        if self.locked[p] || self.locked[old_p] {
+            return Err(String::from("Letter is locked"));
+        }
+
+        self.checkpoint();
+        self.key.swap(p, old_p);
+
+        Ok(())
+    }
+
+    // Swaps what two plain letters are encrypted to, so they trade places in the decrypt
+    pub fn swap(&mut self, p1 : char, p2 : char) -> Result<(), String> {
+        let (p1, p2) = (letter(p1)?, letter(p2)?);
+
+        if self.locked[p1] || self.locked[p2] {
+            return Err(String::from("Letter is locked"));
+        }
+
+        self.checkpoint();
+        self.key.swap(p1, p2);
+
+        Ok(())
+    }
+
+    pub fn set_locked(&mut self, plain : &str, locked : bool) -> Result<(), String> {
+        let letters = plain.chars().map(letter).collect::<Result<Vec<_>, _>>()?;
+
+        self.checkpoint();
+
+        for c in letters {
+            self.locked[c] = locked;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_key(&mut self, key : &str) {
+        self.checkpoint();
+        self.key = Vec::from(key);
+    }
+
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some((key, locked)) => {
+                self.key = key;
+                self.locked = locked;
+                true
+            },
+            None => false
+        }
+    }
+
+    // The key line then the locked plain letters
+    pub fn save(&self, filename : &str) -> Result<(), String> {
+        let locked : String = ALPHABET.chars().filter(|c| self.locked[letter(*c).unwrap()]).collect();
+
+        File::create(filename)
+            .and_then(|mut f| writeln!(f, "{}\n{}", self.key(), locked))
+            .map_err(|e| format!("Cannot write {}: {}", filename, e))
+    }
+
+    pub fn load(&mut self, filename : &str) -> Result<(), String> {
+        let file = File::open(filename).map_err(|e| format!("Cannot open {}: {}", filename, e))?;
+        let lines : Vec<String> = BufReader::new(file).lines().map(|l| l.unwrap()).collect();
+
+        let key = lines.first().map(|k| k.trim().to_uppercase()).unwrap_or_default();
+        let mut sorted : Vec<u8> = Vec::from(key.as_str());
+        sorted.sort();
+
+        if sorted != ALPHABET.as_bytes() {
+            return Err(format!("Bad key in {}", filename));
+        }
+
+        let mut locked = [false; 26];
+
+        for c in lines.get(1).map(|l| l.trim()).unwrap_or("").chars() {
+            locked[letter(c)?] = true;
+        }
+
+        self.checkpoint();
+        self.key = key.into_bytes();
+        self.locked = locked;
+
+        Ok(())
+    }
+
+    // The keyspace of the unlocked letters, starting from the current key
+    pub fn keyspace<'a>(&self, inner : &'a Substitution) -> LockedSubstitution<'a> {
+        let free = (0..26).filter(|p| !self.locked[*p]).collect();
+
+        LockedSubstitution { inner, key: self.key.clone(), free }
+    }
+}
+
+// Substitution keys that only move the unlocked plain letters of a key
+pub struct LockedSubstitution<'a> {
+    inner : &'a Substitution,
+    key : Vec<u8>,
+    free : Vec<usize>,
+}
+
+impl<'a> KeySpace for LockedSubstitution<'a> {
+    // The current key with a few swaps, so runs refine the hand made key rather than start over
+    fn generate_key(&self, rng : &mut dyn RngCore) -> String {
+        (0..START_SWAPS).fold(String::from_utf8(self.key.clone()).unwrap(), |key, _| self.random_swap_key(&key, rng))
+    }
+
+    fn random_swap_key(&self, key : &str, rng : &mut dyn RngCore) -> String {
+        let mut key = Vec::from(key);
+
+        if self.free.len() >= 2 {
+            let idxs = rand::seq::index::sample(rng, self.free.len(), 2);
+            key.swap(self.free[idxs.index(0)], self.free[idxs.index(1)]);
+        }
+
+        String::from_utf8(key).unwrap()
+    }
+
+    fn decrypt(&self, key : &str) -> String {
+        self.inner.decrypt(key)
+    }
+
+    fn score(&self, ng_score : &NgramScore4, wl_score : &WordListScore, text : &str) -> f64 {
+        self.inner.score(ng_score, wl_score, text)
+    }
+
+    fn coverage(&self, wl_score : &WordListScore, text : &str) -> f32 {
+        self.inner.coverage(wl_score, text)
+    }
+}
@@ -1,7 +1,39 @@
+extern crate rand;
+
 use std::fmt;
 use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
 
+use rand::{Rng, RngCore};
+
+use crate::calibrate::{Calibration, Rating};
+use crate::cancel::CancelToken;
+use crate::score::{self, NgramScore4, WordListScore};
+use crate::segment::Segmenter;
+
+// A cipher text together with the keys that can decrypt it
+pub trait KeySpace {
+    fn generate_key(&self, rng : &mut dyn RngCore) -> String;
+
+    // A key close to key, the neighbour move of the annealing
+    fn random_swap_key(&self, key : &str, rng : &mut dyn RngCore) -> String;
+
+    fn decrypt(&self, key : &str) -> String;
+
+    fn score(&self, ng_score : &NgramScore4, wl_score : &WordListScore, text : &str) -> f64 {
+        score::score_text(ng_score, wl_score, text)
+    }
+
+    fn coverage(&self, wl_score : &WordListScore, text : &str) -> f32 {
+        wl_score.coverage(text)
+    }
+}
+
+pub struct AnnealConfig {
+    pub max_fail : u64,
+    pub temp : i64,
+}
+
 #[derive(Default)]
 #[derive(Clone)]
 pub struct SimulatedAnnResult {
@@ -9,11 +41,28 @@ pub struct SimulatedAnnResult {
     pub decrypt : String,
     pub score : f64,
     pub word_coverage : f32,
+    // Decrypt split into words and the log10 probability of the split, empty until segment is called
+    pub segmented : String,
+    pub segment_score : f64,
+    // How English the quadgram score of the decrypt is, None until rate is called
+    pub rating : Option<Rating>,
+}
+
+impl SimulatedAnnResult {
+    pub fn segment(&mut self, segmenter : &Segmenter) {
+        let seg = segmenter.segment(&self.decrypt);
+        self.segmented = seg.to_string();
+        self.segment_score = seg.log_prob;
+    }
+
+    pub fn rate(&mut self, calibration : &Calibration, ng_score : &NgramScore4) {
+        self.rating = Some(calibration.rate_text(ng_score, &self.decrypt));
+    }
 }
 
 impl PartialEq for SimulatedAnnResult {
     fn eq(&self, other: &Self) -> bool {
-        (self.score == other.score)
+        self.score == other.score
     }
 }
 
@@ -21,13 +70,7 @@ impl Eq for SimulatedAnnResult {}
 
 impl PartialOrd for SimulatedAnnResult {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.score > other.score {
-            Some(Ordering::Greater)
-        } else if self.score < other.score {
-            Some(Ordering::Less)
-        } else {
-            Some(Ordering::Equal)
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -45,8 +88,132 @@ impl Ord for SimulatedAnnResult {
 
 impl Display for SimulatedAnnResult {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "SimAnnResult ( Key = {} Score = {} Coverage = {} Decrypt = {} )",
-            self.key, self.score, self.word_coverage, self.decrypt)
+        let rating = self.rating.map(|r| format!(" Rating = {}", r)).unwrap_or_default();
+
+        if self.segmented.is_empty() {
+            write!(f, "SimAnnResult ( Key = {} Score = {} Coverage = {}{} Decrypt = {} )",
+                self.key, self.score, self.word_coverage, rating, self.decrypt)
+        } else {
+            write!(f, "SimAnnResult ( Key = {} Score = {} Coverage = {}{} Segment Score = {:.1} Decrypt = {} )",
+                self.key, self.score, self.word_coverage, rating, self.segment_score, self.segmented)
+        }
     }
 }
 
+
+pub fn simulated_annealing<K>(keyspace : &K,
+    ng_score: &NgramScore4,
+    wl_score: &WordListScore,
+    config: &AnnealConfig) -> SimulatedAnnResult
+    where K : KeySpace + ?Sized {
+    simulated_annealing_cancel(keyspace, ng_score, wl_score, config, &CancelToken::new()).unwrap()
+}
+
+// As simulated_annealing but gives up with None once the token is cancelled
+pub fn simulated_annealing_cancel<K>(keyspace : &K,
+    ng_score: &NgramScore4,
+    wl_score: &WordListScore,
+    config: &AnnealConfig,
+    cancel: &CancelToken) -> Option<SimulatedAnnResult>
+    where K : KeySpace + ?Sized {
+    let mut rng = rand::thread_rng();
+
+    let mut last_key = keyspace.generate_key(&mut rng);
+    let mut last_decrypt = keyspace.decrypt(&last_key);
+    let mut last_score = keyspace.score(ng_score, wl_score, &last_decrypt);
+    let mut best_key = last_key.clone();
+    let mut best_decrypt = last_decrypt.clone();
+    let mut best_score = last_score;
+
+    for temp in 0..config.temp {
+        let mut fail_count = 0;
+
+        while fail_count < config.max_fail {
+            if cancel.is_cancelled() {
+                return None;
+            }
+
+            let cur_key = keyspace.random_swap_key(&last_key, &mut rng);
+
+            let cur_decrypt = keyspace.decrypt(&cur_key);
+
+            let cur_score = keyspace.score(ng_score, wl_score, &cur_decrypt);
+            if cur_score > last_score {
+                last_key = cur_key;
+                last_decrypt = cur_decrypt;
+                last_score = cur_score;
+            } else {
+                let pow = (cur_score - last_score) / (config.temp - temp) as f64;
+                let prob = pow.exp();
+
+                if prob > rng.gen() {
+                    last_key = cur_key;
+                    last_decrypt = cur_decrypt;
+                    last_score = cur_score;
+                }
+            }
+
+            if last_score > best_score {
+                best_key = last_key.clone();
+                best_decrypt = last_decrypt.clone();
+                best_score = last_score;
+                fail_count = 0;
+            } else {
+                fail_count += 1;
+            }
+        }
+    }
+
+    let word_coverage = keyspace.coverage(wl_score, &best_decrypt);
+
+    Some(SimulatedAnnResult {
+        key: best_key,
+        decrypt: best_decrypt,
+        score: best_score,
+        word_coverage,
+        ..Default::default()
+    })
+}
+
+// The result of a key found elsewhere, scored against this keyspace
+pub fn evaluate_key<K>(keyspace : &K,
+    ng_score: &NgramScore4,
+    wl_score: &WordListScore,
+    key : &str) -> SimulatedAnnResult
+    where K : KeySpace + ?Sized {
+    let decrypt = keyspace.decrypt(key);
+    let score = keyspace.score(ng_score, wl_score, &decrypt);
+    let word_coverage = keyspace.coverage(wl_score, &decrypt);
+
+    SimulatedAnnResult {
+        key: String::from(key),
+        decrypt,
+        score,
+        word_coverage,
+        ..Default::default()
+    }
+}
+
+// Keeps all_results sorted best first with at most max_results entries
+pub fn handle_annealing_result(all_results: &mut Vec<SimulatedAnnResult>,
+    new_res: SimulatedAnnResult, max_results: usize) {
+
+    match all_results.binary_search_by(|a| new_res.cmp(a)) {
+        Ok(idx) => all_results[idx] = new_res,
+        Err(idx) => all_results.insert(idx, new_res)
+    };
+
+    if all_results.len() > max_results {
+        all_results.resize(max_results, Default::default());
+    }
+}
+
+pub fn print_results(all_results: &[SimulatedAnnResult]) {
+    println!("Periodic Results: ");
+
+    for i in all_results.iter().enumerate() {
+        println!("{}: {}\n", i.0, i.1);
+    }
+
+    println!("End Results");
+}
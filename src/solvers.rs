@@ -0,0 +1,255 @@
+extern crate rand;
+
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use crate::calibrate::Calibration;
+use crate::cancel::CancelToken;
+use crate::fitness::Fitness;
+use crate::foursquare::{FourSquare, SquareCipher};
+use crate::fractionated::{Fractionated, Fractionation};
+use crate::homophonic::{self, Homophonic};
+use crate::playfair::Playfair;
+use crate::score::{NgramScore4, WordListScore};
+use crate::segment::Segmenter;
+use crate::simann::{self, AnnealConfig, KeySpace, SimulatedAnnResult};
+use crate::stop::StopCriteria;
+use crate::substitution::Substitution;
+use crate::text::CipherText;
+use crate::threads::{self, ThreadConfig};
+
+// Cipher types that can be solved by name, for the binaries that take any of them
+pub const CIPHERS : &str = "substitution,playfair,homophonic,bifid,trifid,foursquare,twosquare,twosquare-vertical";
+
+pub type BoxKeySpace = Arc<dyn KeySpace + Send + Sync>;
+
+// Decrypts shorter than this have no quadgrams to score
+pub const MIN_LETTERS : usize = 4;
+
+// Best results kept and how often they are printed by run
+const MAX_RESULTS : usize = 16;
+const PRINT_SECS : u64 = 20;
+
+// The annealing settings of p1, p2, homophonic, bifid and foursquare
+pub fn default_config(cipher : &str) -> AnnealConfig {
+    match cipher {
+        "substitution" => AnnealConfig { max_fail: 80, temp: 10 },
+        "playfair" | "bifid" | "trifid" => AnnealConfig { max_fail: 800, temp: 20 },
+        _ => AnnealConfig { max_fail: 2000, temp: 20 }
+    }
+}
+
+pub fn default_fitness(cipher : &str) -> &'static str {
+    match cipher {
+        "homophonic" => homophonic::DEFAULT_FITNESS,
+        _ => "blend"
+    }
+}
+
+// The keyspace of a cipher text, with the default fitness of the cipher type unless one is given
+pub fn keyspace(cipher : &str, text : &str, fitness_spec : Option<&str>) -> Result<BoxKeySpace, String> {
+    let fitness = Fitness::create(fitness_spec.unwrap_or_else(|| default_fitness(cipher)),
+        "english_bigrams.txt", "english_quadgrams.txt")?;
+
+    if !text.chars().any(|c| c.is_ascii_alphanumeric()) {
+        return Err(String::from("Cipher text is empty"));
+    }
+
+    let keyspace : BoxKeySpace = match cipher {
+        "substitution" => Arc::new(Substitution::new(CipherText::parse(text), fitness)),
+        "playfair" => Arc::new(Playfair::new(playfair_text(text)?, fitness)),
+        "homophonic" => Arc::new(Homophonic::new(text, None, fitness)?),
+        "bifid" => Arc::new(Fractionated::new(Fractionation::Bifid, text, None, fitness)),
+        "trifid" => Arc::new(Fractionated::new(Fractionation::Trifid, text, None, fitness)),
+        "foursquare" => Arc::new(FourSquare::new(SquareCipher::FourSquare, CipherText::parse(text), fitness)),
+        "twosquare" => Arc::new(FourSquare::new(SquareCipher::TwoSquare, CipherText::parse(text), fitness)),
+        "twosquare-vertical" => Arc::new(FourSquare::new(SquareCipher::TwoSquareVertical, CipherText::parse(text), fitness)),
+        _ => return Err(format!("Unknown cipher {}, expected one of {}", cipher, CIPHERS))
+    };
+
+    let len = keyspace.decrypt(&keyspace.generate_key(&mut rand::thread_rng())).len();
+
+    if len < MIN_LETTERS {
+        return Err(format!("Cipher text has {} letters, at least {} are needed", len, MIN_LETTERS));
+    }
+
+    Ok(keyspace)
+}
+
+// Playfair cipher text is whole pairs of letters from the square, which has no J
+pub fn playfair_text(text : &str) -> Result<CipherText, String> {
+    let cipher = CipherText::parse(text);
+
+    if !cipher.letters.len().is_multiple_of(2) {
+        Err(format!("Playfair cipher text has an odd number of letters ({})", cipher.letters.len()))
+    } else if cipher.letters.contains('J') {
+        Err(String::from("Playfair cipher text cannot contain J"))
+    } else {
+        Ok(cipher)
+    }
+}
+
+// Whether a key from outside could have come from the keyspace, so decrypting it is safe
+pub fn check_key(cipher : &str, keyspace : &BoxKeySpace, key : &str) -> bool {
+    let mut sample = Vec::from(keyspace.generate_key(&mut rand::thread_rng()));
+
+    if key.len() != sample.len() {
+        return false;
+    }
+
+    // Homophonic keys can use any letter for any symbol, the others are permutations
+    match cipher {
+        "homophonic" => key.bytes().all(|c| c.is_ascii_uppercase()),
+        _ => {
+            let mut key = Vec::from(key);
+            key.sort();
+            sample.sort();
+            key == sample
+        }
+    }
+}
+
+// Worker threads annealing a keyspace until cancelled, counting the restarts of each
+pub struct Workers {
+    pub restarts : Arc<Vec<AtomicU64>>,
+    handles : Vec<JoinHandle<()>>,
+}
+
+impl Workers {
+    // Waits for the workers and returns how many of them panicked
+    pub fn join(self) -> usize {
+        self.handles.into_iter().map(|t| t.join()).filter(Result::is_err).count()
+    }
+}
+
+// Starts threads.threads workers, their results arrive on the receiver until they have all stopped
+pub fn spawn_workers<K>(keyspace : &Arc<K>, ng_score : &Arc<NgramScore4>, wl_score : &Arc<WordListScore>,
+    config : &AnnealConfig, threads : &ThreadConfig, cancel : &CancelToken) -> (Receiver<SimulatedAnnResult>, Workers)
+    where K : KeySpace + Send + Sync + ?Sized + 'static {
+
+    let (tx_chan, rx_chan) = mpsc::sync_channel(threads.threads * 4);
+    let restarts = Arc::new(threads::counters(threads.threads));
+    let config = Arc::new(AnnealConfig { max_fail: config.max_fail, temp: config.temp });
+    let mut handles = Vec::new();
+
+    for i in 0..threads.threads {
+        let tx_chan = tx_chan.clone();
+        let keyspace = keyspace.clone();
+        let ngram_score = ng_score.clone();
+        let wl_score = wl_score.clone();
+        let config = config.clone();
+        let cancel = cancel.clone();
+        let threads = threads.clone();
+        let restarts = restarts.clone();
+
+        handles.push(thread::spawn(move || {
+            threads.setup_worker(i);
+
+            while let Some(res) = simann::simulated_annealing_cancel(&*keyspace, &ngram_score, &wl_score, &config, &cancel) {
+                restarts[i].fetch_add(1, Ordering::Relaxed);
+
+                if tx_chan.send(res).is_err() {
+                    return;
+                }
+            }
+        }));
+    }
+
+    (rx_chan, Workers { restarts, handles })
+}
+
+// An annealing run of p1, p2, homophonic, bifid or foursquare, printing the best results as they arrive
+pub struct Run<K : ?Sized> {
+    pub keyspace : Arc<K>,
+    pub ng_score : Arc<NgramScore4>,
+    pub wl_score : Arc<WordListScore>,
+    pub config : AnnealConfig,
+    pub segmenter : Segmenter,
+    pub calibration : Calibration,
+    pub criteria : StopCriteria,
+    // Results with more word coverage than this are printed as they arrive
+    pub high_coverage : f32,
+    // Adjusts the score of each result once the stop criteria have seen it
+    pub rescore : Option<fn(&mut SimulatedAnnResult)>,
+}
+
+impl<K> Run<K> where K : KeySpace + Send + Sync + ?Sized + 'static {
+    // Anneals until cancelled or the stop criteria are met and returns the exit status
+    pub fn run(self, threads : &ThreadConfig, cancel : &CancelToken, print_best : fn(&K, &[SimulatedAnnResult])) -> i32 {
+        if !self.criteria.is_empty() {
+            println!("Stop when {}", self.criteria);
+        }
+
+        println!("Threads = {}", threads);
+
+        let (rx_chan, workers) = spawn_workers(&self.keyspace, &self.ng_score, &self.wl_score, &self.config,
+            threads, cancel);
+
+        let collector_threads = threads.clone();
+        let collector_cancel = cancel.clone();
+        let restarts = workers.restarts.clone();
+        let collector = thread::spawn(move || {
+            collector_threads.setup_collector();
+            self.collect(rx_chan, &collector_cancel, &restarts, print_best);
+        });
+
+        let mut failed = false;
+        let panicked = workers.join();
+
+        if panicked > 0 {
+            println!("{} worker threads panicked", panicked);
+            failed = true;
+        }
+
+        failed |= collector.join().map_err(|_| println!("Collector thread panicked")).is_err();
+
+        std::io::stdout().flush().unwrap();
+        cancel.exit_status(failed)
+    }
+
+    fn collect(&self, rx_chan : Receiver<SimulatedAnnResult>, cancel : &CancelToken, restarts : &[AtomicU64],
+        print_best : fn(&K, &[SimulatedAnnResult])) {
+
+        let mut best_heap = Vec::<SimulatedAnnResult>::new();
+        let mut last_restarts = vec![0; restarts.len()];
+        let mut rate_time = SystemTime::now();
+        let mut print_time = SystemTime::now() + Duration::from_secs(PRINT_SECS);
+        let mut stop = self.criteria.start(cancel);
+
+        while let Some(mut res) = stop.next(&rx_chan) {
+            res.segment(&self.segmenter);
+            res.rate(&self.calibration, &self.ng_score);
+
+            if res.word_coverage > self.high_coverage {
+                println!("High Word Coverage: {}", res);
+            }
+
+            stop.check(&self.ng_score, &res);
+
+            if let Some(rescore) = self.rescore {
+                rescore(&mut res);
+            }
+
+            simann::handle_annealing_result(&mut best_heap, res, MAX_RESULTS);
+
+            if print_time < SystemTime::now() {
+                print_time = SystemTime::now() + Duration::from_secs(PRINT_SECS);
+
+                simann::print_results(&best_heap);
+                print_best(&self.keyspace, &best_heap);
+
+                let secs = rate_time.elapsed().unwrap_or_default().as_secs_f64();
+                rate_time = SystemTime::now();
+
+                threads::print_rates("Worker", "Results", restarts, &mut last_restarts, secs);
+            }
+        }
+
+        simann::print_results(&best_heap);
+        print_best(&self.keyspace, &best_heap);
+    }
+}
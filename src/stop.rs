@@ -0,0 +1,241 @@
+use std::fmt;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancelToken;
+use crate::score::NgramScore4;
+use crate::simann::SimulatedAnnResult;
+use crate::threads::take_option;
+
+// How often the cancel token and the time budget are looked at while waiting for results
+const POLL_MILLIS : u64 = 200;
+// How long results still arriving after a stop are taken before giving up on the workers
+const DRAIN_SECS : u64 = 5;
+
+// When a solver gives up on finding better results, any criterion met stops it
+#[derive(Default)]
+pub struct StopCriteria {
+    // Quadgram score of the decrypt at most this many times the expected English score, English
+    // text is about 1.0 and random letters about 2.0
+    pub score_ratio : Option<f64>,
+    pub coverage : Option<f32>,
+    // Restarts that ended on the best decrypt so far
+    pub agree : Option<usize>,
+    pub time : Option<Duration>,
+    pub crib : Option<String>,
+}
+
+pub enum StopReason {
+    Score(f64),
+    Coverage(f32),
+    Agree(usize),
+    Time(Duration),
+    Crib(String),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::Score(ratio) => write!(f, "score {:.3} times the expected English score", ratio),
+            StopReason::Coverage(coverage) => write!(f, "word coverage {:.2}", coverage),
+            StopReason::Agree(n) => write!(f, "{} restarts agree on the best decrypt", n),
+            StopReason::Time(time) => write!(f, "time budget of {}s used", time.as_secs()),
+            StopReason::Crib(crib) => write!(f, "crib {} found", crib)
+        }
+    }
+}
+
+impl StopCriteria {
+    // Takes --stop-score RATIO, --stop-coverage C, --stop-agree K, --stop-time SECS and --crib TEXT
+    // out of the arguments
+    pub fn from_args(args : &mut Vec<String>) -> Result<Self, String> {
+        Ok(StopCriteria {
+            score_ratio: take_option(args, "--stop-score")?,
+            coverage: take_option(args, "--stop-coverage")?,
+            agree: take_option(args, "--stop-agree")?,
+            time: take_option::<u64>(args, "--stop-time")?.map(Duration::from_secs),
+            crib: take_option::<String>(args, "--crib")?
+                .map(|c| c.chars().filter(|c| c.is_ascii_alphabetic()).collect::<String>().to_uppercase()),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.score_ratio.is_none() && self.coverage.is_none() && self.agree.is_none()
+            && self.time.is_none() && self.crib.is_none()
+    }
+
+    // Met criteria and the time budget cancel the token
+    pub fn start(&self, cancel : &CancelToken) -> StopCheck<'_> {
+        StopCheck {
+            criteria: self,
+            cancel: cancel.clone(),
+            start: Instant::now(),
+            drain_until: None,
+            best_score: f64::MIN,
+            best_decrypt: String::new(),
+            agree: 0,
+            expected: None,
+        }
+    }
+}
+
+impl fmt::Display for StopCriteria {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let mut terms = Vec::new();
+
+        if let Some(ratio) = self.score_ratio {
+            terms.push(format!("score ratio <= {}", ratio));
+        }
+
+        if let Some(coverage) = self.coverage {
+            terms.push(format!("coverage >= {}", coverage));
+        }
+
+        if let Some(agree) = self.agree {
+            terms.push(format!("{} restarts agree", agree));
+        }
+
+        if let Some(time) = self.time {
+            terms.push(format!("{}s", time.as_secs()));
+        }
+
+        if let Some(crib) = &self.crib {
+            terms.push(format!("crib {}", crib));
+        }
+
+        if terms.is_empty() {
+            write!(f, "never")
+        } else {
+            write!(f, "{}", terms.join(" or "))
+        }
+    }
+}
+
+// The criteria applied to the results of one run
+pub struct StopCheck<'a> {
+    criteria : &'a StopCriteria,
+    cancel : CancelToken,
+    start : Instant,
+    // Set once the token is cancelled
+    drain_until : Option<Instant>,
+    best_score : f64,
+    best_decrypt : String,
+    agree : usize,
+    // English quadgram score at a decrypt length, a walk of the whole table so it is kept
+    expected : Option<(usize, f64)>,
+}
+
+impl<'a> StopCheck<'a> {
+    // Time left before the time budget runs out, None without one
+    pub fn time_left(&self) -> Option<Duration> {
+        self.criteria.time.map(|t| t.checked_sub(self.start.elapsed()).unwrap_or_default())
+    }
+
+    pub fn timed_out(&self) -> Option<StopReason> {
+        match self.time_left() {
+            Some(left) if left == Duration::from_secs(0) => Some(StopReason::Time(self.criteria.time.unwrap())),
+            _ => None
+        }
+    }
+
+    // The next result. Once the run is cancelled, results the workers still send are taken for
+    // DRAIN_SECS, None when they are all gone or the time is up
+    pub fn next(&mut self, rx_chan : &Receiver<SimulatedAnnResult>) -> Option<SimulatedAnnResult> {
+        loop {
+            if let Some(deadline) = self.drain_until {
+                let left = deadline.checked_duration_since(Instant::now())?;
+                return rx_chan.recv_timeout(left).ok();
+            }
+
+            if self.cancel.is_cancelled() {
+                self.drain_until = Some(Instant::now() + Duration::from_secs(DRAIN_SECS));
+                continue;
+            }
+
+            if let Some(reason) = self.timed_out() {
+                self.stop(reason);
+                continue;
+            }
+
+            let poll = Duration::from_millis(POLL_MILLIS);
+            let poll = self.time_left().map_or(poll, |left| left.min(poll));
+
+            match rx_chan.recv_timeout(poll) {
+                Ok(res) => return Some(res),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return None
+            }
+        }
+    }
+
+    fn stop(&self, reason : StopReason) {
+        println!("Stopping, {}", reason);
+        self.cancel.cancel();
+    }
+
+    // Checks a new result before any rescoring and stops the run when a criterion is met,
+    // results drained after a stop are not checked
+    pub fn check(&mut self, ng_score : &NgramScore4, res : &SimulatedAnnResult) -> bool {
+        if self.cancel.is_cancelled() {
+            return false;
+        }
+
+        match self.met(ng_score, res) {
+            Some(reason) => {
+                self.stop(reason);
+                true
+            },
+            None => false
+        }
+    }
+
+    fn met(&mut self, ng_score : &NgramScore4, res : &SimulatedAnnResult) -> Option<StopReason> {
+        let criteria = self.criteria;
+
+        if res.decrypt == self.best_decrypt {
+            self.agree += 1;
+        } else if res.score > self.best_score {
+            self.best_score = res.score;
+            self.best_decrypt = res.decrypt.clone();
+            self.agree = 1;
+        }
+
+        if let Some(crib) = &criteria.crib {
+            if res.decrypt.contains(crib.as_str()) {
+                return Some(StopReason::Crib(crib.clone()));
+            }
+        }
+
+        if let Some(coverage) = criteria.coverage {
+            if res.word_coverage >= coverage {
+                return Some(StopReason::Coverage(res.word_coverage));
+            }
+        }
+
+        if let Some(max_ratio) = criteria.score_ratio {
+            let len = res.decrypt.len();
+            let expected = match self.expected {
+                Some((expected_len, expected)) if expected_len == len => expected,
+                _ => {
+                    let expected = ng_score.expected_score(len);
+                    self.expected = Some((len, expected));
+                    expected
+                }
+            };
+
+            let ratio = ng_score.score(&res.decrypt) / expected;
+
+            if ratio <= max_ratio {
+                return Some(StopReason::Score(ratio));
+            }
+        }
+
+        if let Some(agree) = criteria.agree {
+            if self.agree >= agree {
+                return Some(StopReason::Agree(self.agree));
+            }
+        }
+
+        self.timed_out()
+    }
+}
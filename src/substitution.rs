@@ -0,0 +1,135 @@
+extern crate rand;
+
+use rand::RngCore;
+use rand::seq::SliceRandom;
+
+use crate::fitness::Fitness;
+use crate::freq::ENGLISH_FREQS;
+use crate::score::{NgramScore4, WordListScore};
+use crate::simann::KeySpace;
+use crate::text::CipherText;
+
+pub const ALPHABET : &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+// Keys hold the cipher letter for each plain letter, key[plain] = cipher
+// Anything that is not in the key is passed through
+pub fn decrypt(cipher : &str, key : &str) -> String {
+    assert_eq!(key.len(), ALPHABET.len());
+    let cipher = cipher.to_uppercase();
+    let mut decrypted = String::with_capacity(cipher.len());
+
+    for c in cipher.chars() {
+        match key.find(c) {
+            Some(c_idx) => decrypted.push(ALPHABET.chars().nth(c_idx).unwrap()),
+            None => decrypted.push(c)
+        }
+    }
+
+    decrypted
+}
+
+pub fn encrypt(plain : &str, key : &str) -> String {
+    assert_eq!(key.len(), ALPHABET.len());
+
+    plain.to_uppercase().chars()
+        .map(|c| match ALPHABET.find(c) {
+            Some(idx) => key.as_bytes()[idx] as char,
+            None => c
+        })
+        .collect()
+}
+
+pub fn random_key(rng : &mut dyn RngCore) -> String {
+    let mut key = Vec::from(ALPHABET);
+    key.shuffle(rng);
+
+    String::from_utf8(key).unwrap()
+}
+
+// The key mapping the most frequent cipher letters to the most frequent English letters
+pub fn frequency_key(cipher : &str) -> String {
+    let mut counts = [0; 26];
+    for b in cipher.bytes().filter(|b| b.is_ascii_uppercase()) {
+        counts[(b - b'A') as usize] += 1;
+    }
+
+    let mut by_count : Vec<u8> = ALPHABET.bytes().collect();
+    by_count.sort_by_key(|c| std::cmp::Reverse(counts[(c - b'A') as usize]));
+
+    let mut by_freq : Vec<usize> = (0..26).collect();
+    by_freq.sort_by(|a, b| ENGLISH_FREQS[*b].partial_cmp(&ENGLISH_FREQS[*a]).unwrap());
+
+    let mut key = [0; 26];
+    for (p, c) in by_freq.iter().zip(by_count) {
+        key[*p] = c;
+    }
+
+    String::from_utf8(key.to_vec()).unwrap()
+}
+
+// Simple substitution of the letters of a cipher text
+pub struct Substitution {
+    pub cipher : CipherText,
+    // Score whole words, only when asked for as spaces may also be transmission groups
+    use_words : bool,
+    fitness : Fitness,
+}
+
+impl Substitution {
+    pub fn new(cipher : CipherText, fitness : Fitness) -> Self {
+        Substitution { cipher, use_words: false, fitness }
+    }
+
+    // Scores word list hits on the words of the cipher, if it keeps its word boundaries
+    pub fn with_words(mut self, use_words : bool) -> Self {
+        self.use_words = use_words && self.cipher.has_word_boundaries();
+        self
+    }
+}
+
+impl KeySpace for Substitution {
+    fn generate_key(&self, rng : &mut dyn RngCore) -> String {
+        random_key(rng)
+    }
+
+    fn random_swap_key(&self, key : &str, rng : &mut dyn RngCore) -> String {
+        let mut key = Vec::from(key);
+        let idxs = rand::seq::index::sample(rng, key.len(), 2);
+
+        key.swap(idxs.index(0), idxs.index(1));
+
+        String::from_utf8(key).unwrap()
+    }
+
+    fn decrypt(&self, key : &str) -> String {
+        decrypt(&self.cipher.letters, key)
+    }
+
+    fn score(&self, ng_score : &NgramScore4, wl_score : &WordListScore, text : &str) -> f64 {
+        let words = if self.use_words { Some(self.cipher.words()) } else { None };
+
+        self.fitness.score(ng_score, wl_score, text, words)
+    }
+
+    fn coverage(&self, wl_score : &WordListScore, text : &str) -> f32 {
+        if self.use_words {
+            wl_score.word_coverage(text, self.cipher.words())
+        } else {
+            wl_score.coverage(text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY : &str = "QWERTYUIOPASDFGHJKLZXCVBNM";
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        let cipher = encrypt("Attack at dawn!", KEY);
+        assert_eq!(cipher, "QZZQEA QZ RQVF!");
+        assert_eq!(decrypt(&cipher, KEY), "ATTACK AT DAWN!");
+    }
+}
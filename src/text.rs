@@ -0,0 +1,108 @@
+extern crate rand;
+
+use std::ops::Range;
+
+use rand::{Rng, RngCore};
+
+enum Token {
+    Letter(bool),
+    Other(char),
+}
+
+// Cipher text split into the letters the solvers work on and everything else,
+// so word boundaries, digits and punctuation can be restored after decrypting
+pub struct CipherText {
+    pub letters : String,
+    tokens : Vec<Token>,
+    words : Vec<Range<usize>>,
+}
+
+impl CipherText {
+    pub fn parse(text : &str) -> Self {
+        let mut letters = String::with_capacity(text.len());
+        let mut tokens = Vec::with_capacity(text.len());
+        let mut words = Vec::new();
+        let mut word_start = None;
+
+        for c in text.trim_end().chars() {
+            if c.is_ascii_alphabetic() {
+                if word_start.is_none() {
+                    word_start = Some(letters.len());
+                }
+
+                letters.push(c.to_ascii_uppercase());
+                tokens.push(Token::Letter(c.is_ascii_lowercase()));
+            } else {
+                if let Some(start) = word_start.take() {
+                    words.push(start..letters.len());
+                }
+
+                tokens.push(Token::Other(c));
+            }
+        }
+
+        if let Some(start) = word_start {
+            words.push(start..letters.len());
+        }
+
+        CipherText { letters, tokens, words }
+    }
+
+    // Ranges of letters that form words in the original text
+    pub fn words(&self) -> &[Range<usize>] {
+        &self.words
+    }
+
+    pub fn has_word_boundaries(&self) -> bool {
+        self.words.len() > 1
+    }
+
+    // Puts the decrypted letters back in the original layout, keeping the case of each letter
+    pub fn render(&self, decrypt : &str) -> String {
+        assert_eq!(decrypt.len(), self.letters.len(), "Decrypt length does not match the cipher");
+
+        let mut it = decrypt.chars();
+
+        self.tokens.iter()
+            .map(|t| match t {
+                Token::Letter(true) => it.next().unwrap().to_ascii_lowercase(),
+                Token::Letter(false) => it.next().unwrap(),
+                Token::Other(c) => *c
+            })
+            .collect()
+    }
+}
+
+// The letters of a text file, upper cased with everything else dropped
+pub fn read_corpus(filename : &str) -> String {
+    let text = std::fs::read_to_string(filename)
+        .unwrap_or_else(|_| panic!("Cannot open {}", filename));
+
+    CipherText::parse(&text).letters
+}
+
+// Random windows of len letters from the corpus
+pub fn sample_corpus(corpus : &str, len : usize, count : usize, rng : &mut dyn RngCore) -> Result<Vec<String>, String> {
+    if corpus.len() < len {
+        return Err(format!("Corpus has {} letters, samples need {}", corpus.len(), len));
+    }
+
+    Ok((0..count)
+        .map(|_| {
+            let start = rng.gen_range(0, corpus.len() - len + 1);
+            String::from(&corpus[start..start + len])
+        })
+        .collect())
+}
+
+// First word of every line of a word list, upper cased, skipping anything that is not all letters
+pub fn read_word_list(filename : &str) -> Vec<String> {
+    let text = std::fs::read_to_string(filename)
+        .unwrap_or_else(|_| panic!("Cannot open {}", filename));
+
+    text.lines()
+        .filter_map(|l| l.split_ascii_whitespace().next())
+        .map(|w| w.to_uppercase())
+        .filter(|w| w.bytes().all(|b| b.is_ascii_uppercase()))
+        .collect()
+}
@@ -0,0 +1,155 @@
+extern crate core_affinity;
+extern crate rayon;
+extern crate thread_priority;
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use thread_priority::*;
+
+// How many worker threads a solver runs and how they are scheduled
+#[derive(Clone)]
+pub struct ThreadConfig {
+    pub threads : usize,
+    // Worker i runs only on core i, wrapping around when there are more workers than cores
+    pub pin : bool,
+    // Workers only get CPU time nothing else wants, for shared machines
+    pub nice : bool,
+}
+
+// Cores this process may run on
+pub fn detected_cores() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+impl Default for ThreadConfig {
+    fn default() -> Self {
+        ThreadConfig { threads: detected_cores(), pin: false, nice: false }
+    }
+}
+
+// Removes --name VALUE from the arguments
+pub fn take_option<T : std::str::FromStr>(args : &mut Vec<String>, name : &str) -> Result<Option<T>, String> {
+    let i = match args.iter().position(|a| a == name) {
+        Some(i) => i,
+        None => return Ok(None)
+    };
+
+    let value = args.get(i + 1)
+        .and_then(|v| v.parse::<T>().ok())
+        .ok_or_else(|| format!("{} needs a value", name))?;
+    args.drain(i..=i + 1);
+
+    Ok(Some(value))
+}
+
+impl ThreadConfig {
+    // Takes --threads N, --pin and --nice out of the arguments
+    pub fn from_args(args : &mut Vec<String>) -> Result<Self, String> {
+        let mut config = ThreadConfig::default();
+
+        if let Some(i) = args.iter().position(|a| a == "--threads") {
+            config.threads = args.get(i + 1)
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|&n| n > 0)
+                .ok_or_else(|| String::from("--threads needs a number above 0"))?;
+            args.drain(i..=i + 1);
+        }
+
+        for (flag, value) in [("--pin", &mut config.pin), ("--nice", &mut config.nice)] {
+            if let Some(i) = args.iter().position(|a| a == flag) {
+                args.remove(i);
+                *value = true;
+            }
+        }
+
+        Ok(config)
+    }
+
+    // Called first thing on worker thread index
+    pub fn setup_worker(&self, index : usize) {
+        if self.pin {
+            let pinned = core_affinity::get_core_ids()
+                .filter(|cores| !cores.is_empty())
+                .map(|cores| core_affinity::set_for_current(cores[index % cores.len()]))
+                .unwrap_or(false);
+
+            if !pinned {
+                println!("Cannot pin worker {} to a core", index);
+            }
+        }
+
+        if self.nice {
+            set_priority(ThreadPriority::Min, NormalThreadSchedulePolicy::Idle);
+        }
+    }
+
+    // A rayon pool of the configured size for the parallel iterators, set up like the workers
+    pub fn pool(&self) -> rayon::ThreadPool {
+        let config = self.clone();
+
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .start_handler(move |index| config.setup_worker(index))
+            .build()
+            .unwrap_or_else(|e| panic!("Cannot start {} threads: {}", self.threads, e))
+    }
+
+    // The thread collecting results keeps up with the workers, unless they are nice
+    pub fn setup_collector(&self) {
+        if !self.nice {
+            set_priority(ThreadPriority::Max, NormalThreadSchedulePolicy::Normal);
+        }
+    }
+}
+
+fn set_priority(priority : ThreadPriority, policy : NormalThreadSchedulePolicy) {
+    if let Err(e) = set_thread_priority(thread_native_id(), priority, ThreadSchedulePolicy::Normal(policy)) {
+        println!("Cannot set thread priority: {:?}", e);
+    }
+}
+
+impl fmt::Display for ThreadConfig {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} threads on {} cores", self.threads, detected_cores())?;
+
+        if self.pin {
+            write!(f, ", pinned")?;
+        }
+
+        if self.nice {
+            write!(f, ", nice")?;
+        }
+
+        Ok(())
+    }
+}
+
+// One counter per worker thread
+pub fn counters(threads : usize) -> Vec<AtomicU64> {
+    (0..threads).map(|_| AtomicU64::new(0)).collect()
+}
+
+// Prints the rate of every counter since the last call, returns the total rate
+pub fn print_rates(name : &str, unit : &str, counters : &[AtomicU64], last : &mut [u64], secs : f64) -> f64 {
+    let mut total_rate = 0.0;
+
+    print!("{} Rates ({}/s):", name, unit);
+
+    for (i, c) in counters.iter().enumerate() {
+        let cur = c.load(Ordering::Relaxed);
+        let rate = (cur - last[i]) as f64 / secs;
+        last[i] = cur;
+        total_rate += rate;
+
+        if i % 8 == 0 {
+            println!();
+        }
+        print!(" {:>2}: {:>10.1}", i, rate);
+    }
+
+    println!();
+    println!("{} Total Rate = {:.1} {}/s", name, total_rate, unit);
+
+    total_rate
+}